@@ -8,6 +8,7 @@ use r_tracer::spacial::scene::Scene;
 use r_tracer::spacial::camera::Camera;
 use r_tracer::spacial::mesh_object::MeshObject;
 use r_tracer::utilities::file_utilities::{load_model, save_vector2d_as_png, import_texture};
+use r_tracer::utilities::import_options::ImportOptions;
 use std::time::Instant;
 
 
@@ -25,7 +26,7 @@ fn bench_scene() {
         None, Some(1), Some(2), Some(3)
     );
 
-    let rock_sphere = load_model("../Models/rock_sphere.obj", rock);
+    let rock_sphere = load_model("../Models/rock_sphere.obj", rock, &ImportOptions::y_up()).unwrap();
 
     let mut meshes: Vec<MeshObject> = vec![];
     meshes.push(MeshObject::new(rock_sphere, true));
@@ -34,14 +35,14 @@ fn bench_scene() {
     let size_x: usize = 1200;
     let size_y: usize = 800;
 
-    let environment_map: Vector2D<Color> = import_texture("../Textures/HDRIOutdoor/HdrOutdoorResidentialRiverwalkAfternoonClear001_JPG_3K.jpg");
+    let environment_map: Vector2D<Color> = import_texture("../Textures/HDRIOutdoor/HdrOutdoorResidentialRiverwalkAfternoonClear001_JPG_3K.jpg").unwrap();
 
     let mut maps: Vec<Vector2D<Color>> = vec![];
 
-    let rock_col: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_COL_VAR1_3K.jpg");
-    let rock_normal: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_NRM_3K.jpg");
-    let rock_smoothness: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_GLOSS_3K.jpg");
-    let rock_specular: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_REFL_3K.jpg");
+    let rock_col: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_COL_VAR1_3K.jpg").unwrap();
+    let rock_normal: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_NRM_3K.jpg").unwrap();
+    let rock_smoothness: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_GLOSS_3K.jpg").unwrap();
+    let rock_specular: Vector2D<Color> = import_texture("../Textures/CliffGreyChunky008/CliffGreyChunky008_REFL_3K.jpg").unwrap();
 
     maps.push(rock_col);
     maps.push(rock_normal);
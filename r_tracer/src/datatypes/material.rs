@@ -1,6 +1,7 @@
 use crate::datatypes::color::Color;
+use crate::error::RTracerError;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Material {
     pub diffuse_color: Color,
     pub emission_color: Color,
@@ -13,6 +14,7 @@ pub struct Material {
     pub index_of_refraction: f64,
     pub normal_strength: f64,
     pub visible: bool,
+    pub use_vertex_colors: bool,
     pub diffuse_color_map_index: Option<usize>,
     pub emission_color_map_index: Option<usize>,
     pub specular_color_map_index: Option<usize>,
@@ -41,6 +43,7 @@ impl Material {
             index_of_refraction: ior,
             normal_strength: normal_strength,
             visible: visible,
+            use_vertex_colors: false,
             diffuse_color_map_index: diffuse_color_map_id,
             emission_color_map_index: emission_color_map_id,
             specular_color_map_index: specular_color_map_id,
@@ -51,6 +54,10 @@ impl Material {
         }
     }
 
+    pub fn builder() -> MaterialBuilder {
+        MaterialBuilder::new()
+    }
+
     pub fn empty() -> Material {
         Material {
             diffuse_color: Color::black(),
@@ -64,6 +71,7 @@ impl Material {
             index_of_refraction: 0.0,
             normal_strength: 0.0,
             visible: false,
+            use_vertex_colors: false,
             diffuse_color_map_index: None,
             emission_color_map_index: None,
             specular_color_map_index: None,
@@ -73,4 +81,189 @@ impl Material {
             specular_map_index: None
         }
     }
-}
\ No newline at end of file
+}
+
+// Named alternative to Material::new. Starts from the visible light grey diffuse material that meshes
+// without a material library get, and checks the values when built.
+#[derive(Copy, Clone)]
+pub struct MaterialBuilder {
+    material: Material
+}
+
+impl Default for MaterialBuilder {
+    fn default() -> MaterialBuilder {
+        MaterialBuilder::new()
+    }
+}
+
+impl MaterialBuilder {
+    pub fn new() -> MaterialBuilder {
+        MaterialBuilder {
+            material: Material {
+                diffuse_color: Color::white() * 0.8,
+                specular_color: Color::white(),
+                dielectric_color: Color::white(),
+                index_of_refraction: 1.5,
+                normal_strength: 1.0,
+                visible: true,
+                ..Material::empty()
+            }
+        }
+    }
+
+    pub fn with_diffuse_color(mut self, color: Color) -> MaterialBuilder {
+        self.material.diffuse_color = color;
+        self
+    }
+
+    pub fn with_emission(mut self, color: Color, strength: f64) -> MaterialBuilder {
+        self.material.emission_color = color;
+        self.material.emission_strength = strength;
+        self
+    }
+
+    pub fn with_specular_color(mut self, color: Color) -> MaterialBuilder {
+        self.material.specular_color = color;
+        self
+    }
+
+    pub fn with_dielectric_color(mut self, color: Color) -> MaterialBuilder {
+        self.material.dielectric_color = color;
+        self
+    }
+
+    pub fn with_smoothness(mut self, smoothness: f64) -> MaterialBuilder {
+        self.material.smoothness = smoothness;
+        self
+    }
+
+    pub fn with_specular(mut self, specular: f64) -> MaterialBuilder {
+        self.material.specular = specular;
+        self
+    }
+
+    pub fn with_dielectric(mut self, dielectric: f64) -> MaterialBuilder {
+        self.material.dielectric = dielectric;
+        self
+    }
+
+    pub fn with_index_of_refraction(mut self, index_of_refraction: f64) -> MaterialBuilder {
+        self.material.index_of_refraction = index_of_refraction;
+        self
+    }
+
+    pub fn with_normal_strength(mut self, normal_strength: f64) -> MaterialBuilder {
+        self.material.normal_strength = normal_strength;
+        self
+    }
+
+    pub fn with_visible(mut self, visible: bool) -> MaterialBuilder {
+        self.material.visible = visible;
+        self
+    }
+
+    pub fn with_vertex_colors(mut self, use_vertex_colors: bool) -> MaterialBuilder {
+        self.material.use_vertex_colors = use_vertex_colors;
+        self
+    }
+
+    pub fn with_diffuse_color_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.diffuse_color_map_index = Some(index);
+        self
+    }
+
+    pub fn with_emission_color_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.emission_color_map_index = Some(index);
+        self
+    }
+
+    pub fn with_specular_color_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.specular_color_map_index = Some(index);
+        self
+    }
+
+    pub fn with_dielectric_color_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.dielectric_color_map_index = Some(index);
+        self
+    }
+
+    pub fn with_normal_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.normal_map_index = Some(index);
+        self
+    }
+
+    pub fn with_smoothness_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.smoothness_map_index = Some(index);
+        self
+    }
+
+    pub fn with_specular_map(mut self, index: usize) -> MaterialBuilder {
+        self.material.specular_map_index = Some(index);
+        self
+    }
+
+    // `texture_count` is the number of texture maps of the scene the material is for, map indices must be below it
+    pub fn build(self, texture_count: usize) -> Result<Material, RTracerError> {
+        let material: Material = self.material;
+        let colors: [(&str, Color); 4] = [
+            ("diffuse_color", material.diffuse_color),
+            ("emission_color", material.emission_color),
+            ("specular_color", material.specular_color),
+            ("dielectric_color", material.dielectric_color)
+        ];
+        for (name, color) in colors {
+            if [color.red, color.green, color.blue].iter().any(|value: &f64| !value.is_finite() || *value < 0.0) {
+                return Err(RTracerError::invalid_parameter(name, "channels must be finite and not negative"))
+            }
+        }
+
+        check_range("emission_strength", material.emission_strength, 0.0, f64::INFINITY)?;
+        check_range("smoothness", material.smoothness, 0.0, 1.0)?;
+        check_range("specular", material.specular, 0.0, 1.0)?;
+        check_range("dielectric", material.dielectric, 0.0, 1.0)?;
+        check_range("normal_strength", material.normal_strength, f64::NEG_INFINITY, f64::INFINITY)?;
+        check_positive("index_of_refraction", material.index_of_refraction)?;
+
+        let maps: [(&str, Option<usize>); 7] = [
+            ("diffuse_color_map", material.diffuse_color_map_index),
+            ("emission_color_map", material.emission_color_map_index),
+            ("specular_color_map", material.specular_color_map_index),
+            ("dielectric_color_map", material.dielectric_color_map_index),
+            ("normal_map", material.normal_map_index),
+            ("smoothness_map", material.smoothness_map_index),
+            ("specular_map", material.specular_map_index)
+        ];
+        for (name, index) in maps {
+            if let Some(index) = index.filter(|&index: &usize| index >= texture_count) {
+                return Err(RTracerError::invalid_parameter(name,
+                    format!("texture {} does not exist, the scene has {} textures", index, texture_count)))
+            }
+        }
+
+        Ok(material)
+    }
+}
+
+// Finite and within [min, max]
+pub(crate) fn check_range(name: &str, value: f64, min: f64, max: f64) -> Result<(), RTracerError> {
+    if !value.is_finite() {
+        return Err(RTracerError::invalid_parameter(name, format!("must be finite, got {}", value)))
+    }
+    if value < min || value > max {
+        let range: String = match (min.is_finite(), max.is_finite()) {
+            (true, true) => format!("between {} and {}", min, max),
+            (true, false) => format!("at least {}", min),
+            _ => format!("at most {}", max)
+        };
+        return Err(RTracerError::invalid_parameter(name, format!("must be {}, got {}", range, value)))
+    }
+    Ok(())
+}
+
+// Finite and above 0
+pub(crate) fn check_positive(name: &str, value: f64) -> Result<(), RTracerError> {
+    if !(value > 0.0 && value.is_finite()) {
+        return Err(RTracerError::invalid_parameter(name, format!("must be greater than 0, got {}", value)))
+    }
+    Ok(())
+}
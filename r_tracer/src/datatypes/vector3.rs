@@ -1,5 +1,6 @@
 use crate::datatypes::color::Color;
 use crate::datatypes::vector2::Vector2;
+use crate::utilities::sampling;
 use std::f64::consts::PI;
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
@@ -98,27 +99,26 @@ impl Vector3 {
         ).normalize()
     }
     pub fn random_hemisphere_normal(normal: Vector3) -> Vector3 {
-        let mut rng = rand::thread_rng();
         let mut random_vector = Vector3::new(
-            rng.gen::<f64>() * 2.0 - 1.0,
-            rng.gen::<f64>() * 2.0 - 1.0,
-            rng.gen::<f64>() * 2.0 - 1.0,
+            sampling::random() * 2.0 - 1.0,
+            sampling::random() * 2.0 - 1.0,
+            sampling::random() * 2.0 - 1.0,
         ).normalize();
         if random_vector * normal < 0.0 { random_vector = -1.0 * random_vector }
     
         random_vector
     }
     pub fn random_perturb(scale: Vector2) -> Vector3 {
-        let rand_val1: f64 = rand::thread_rng().gen_range(0.0..1.0);
-        let rand_val2: f64 = rand::thread_rng().gen_range(0.0..1.0);
+        let rand_val1: f64 = sampling::random();
+        let rand_val2: f64 = sampling::random();
         let angle: f64 = rand_val1*2.0*PI;
         let circle_pt: Vector2 = Vector2::new(angle.cos(), angle.sin());
         rand_val2.sqrt()*Vector3::new(0.0, circle_pt.y/scale.x, circle_pt.x/scale.y)
     }
     pub fn random_in_unit_disk() -> Vector3 {
         loop {
-            let x = rand::thread_rng().gen_range(-1.0..1.0);
-            let y = rand::thread_rng().gen_range(-1.0..1.0);
+            let x = sampling::random_range(-1.0..1.0);
+            let y = sampling::random_range(-1.0..1.0);
             let point = Vector3 { x, y, z: 0.0 };
             if point.square().component_add() < 1.0 {
                 return point;
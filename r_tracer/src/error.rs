@@ -0,0 +1,76 @@
+use crate::utilities::scene_file::SceneFileError;
+use std::fmt;
+use std::io;
+
+
+#[derive(Debug)]
+pub enum RTracerError {
+    Io { path: String, source: io::Error },
+    UnsupportedFormat { path: String },
+    ModelParse { path: String, message: String },
+    TextureLoad { path: String, source: image::ImageError },
+    EmptyTexture { path: String },
+    ImageWrite { path: String, message: String },
+    Checkpoint { path: String, message: String },
+    IncompatibleRenders { message: String },
+    Metadata { path: String, message: String },
+    InvalidParameter { name: String, message: String },
+    SceneFile(SceneFileError)
+}
+
+impl RTracerError {
+    pub fn io(path: &str, source: io::Error) -> RTracerError {
+        RTracerError::Io { path: path.to_string(), source }
+    }
+
+    pub fn model_parse(path: &str, message: impl ToString) -> RTracerError {
+        RTracerError::ModelParse { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn image_write(path: &str, message: impl ToString) -> RTracerError {
+        RTracerError::ImageWrite { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn checkpoint(path: &str, message: impl ToString) -> RTracerError {
+        RTracerError::Checkpoint { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn invalid_parameter(name: &str, message: impl ToString) -> RTracerError {
+        RTracerError::InvalidParameter { name: name.to_string(), message: message.to_string() }
+    }
+}
+
+impl fmt::Display for RTracerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RTracerError::Io { path, source } => write!(f, "failed to read {}: {}", path, source),
+            RTracerError::UnsupportedFormat { path } => write!(f, "unsupported file extension: {}", path),
+            RTracerError::ModelParse { path, message } => write!(f, "failed to load model {}: {}", path, message),
+            RTracerError::TextureLoad { path, source } => write!(f, "failed to load texture {}: {}", path, source),
+            RTracerError::EmptyTexture { path } => write!(f, "texture has no pixels: {}", path),
+            RTracerError::ImageWrite { path, message } => write!(f, "failed to write image {}: {}", path, message),
+            RTracerError::Checkpoint { path, message } => write!(f, "cannot use checkpoint {}: {}", path, message),
+            RTracerError::IncompatibleRenders { message } => write!(f, "cannot merge renders: {}", message),
+            RTracerError::Metadata { path, message } => write!(f, "cannot read render metadata from {}: {}", path, message),
+            RTracerError::InvalidParameter { name, message } => write!(f, "invalid {}: {}", name, message),
+            RTracerError::SceneFile(err) => err.fmt(f)
+        }
+    }
+}
+
+impl std::error::Error for RTracerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RTracerError::Io { source, .. } => Some(source),
+            RTracerError::TextureLoad { source, .. } => Some(source),
+            RTracerError::SceneFile(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<SceneFileError> for RTracerError {
+    fn from(err: SceneFileError) -> RTracerError {
+        RTracerError::SceneFile(err)
+    }
+}
@@ -1,8 +1,18 @@
+pub mod error;
 
 pub mod utilities {
     pub mod frame_handler;
     pub mod file_utilities;
     pub mod postprocessing;
+    pub mod scene_file;
+    pub mod gltf_utilities;
+    pub mod vertex_welding;
+    pub mod import_options;
+    pub mod obj_export;
+    pub mod mesh_cache;
+    pub mod sampling;
+    pub mod checkpoint;
+    pub mod render_metadata;
 }
 
 pub mod datatypes {
@@ -17,9 +27,18 @@ pub mod datatypes {
 
 pub mod spacial {
     pub mod camera;
+    pub mod camera_builder;
+    pub mod camera_basis;
+    pub mod physical_camera;
+    pub mod aperture;
     pub mod scene;
     pub mod tri;  
     pub mod mesh_object;
+    pub mod motion;
     pub mod ray;
     pub mod bvh;
+    pub mod aov;
+    pub mod cryptomatte;
+    pub mod stereo;
+    pub mod crop_region;
 }
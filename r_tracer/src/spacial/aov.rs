@@ -0,0 +1,169 @@
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::datatypes::hit_point::HitPoint;
+use crate::spacial::scene::Scene;
+use crate::spacial::ray::Ray;
+use crate::error::RTracerError;
+use crate::utilities::file_utilities::{save_vector2d_as_exr, save_vector2d_as_pfm, save_vector2d_as_png16, ExrPrecision};
+use crate::utilities::postprocessing::PostProcessing;
+
+
+// Auxiliary buffers taken from the first hit of the camera rays
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Aov {
+    // Distance along the camera's view axis, or along the primary ray for panoramic projections, infinite
+    // where nothing was hit
+    Depth,
+    // World space normal including the normal map, components in [-1, 1]
+    Normal,
+    // Diffuse color as returned by the material and its maps
+    Albedo,
+    Position,
+    // (u, v, 0)
+    Uv,
+    // Index into MeshObject::unique_materials, -1 where nothing was hit
+    MaterialId,
+    // Index into Scene.meshes, -1 where nothing was hit
+    ObjectId
+}
+
+impl Aov {
+    pub fn all() -> Vec<Aov> {
+        vec![Aov::Depth, Aov::Normal, Aov::Albedo, Aov::Position, Aov::Uv, Aov::MaterialId, Aov::ObjectId]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id"
+        }
+    }
+
+    fn is_id(self) -> bool {
+        self == Aov::MaterialId || self == Aov::ObjectId
+    }
+}
+
+#[derive(Clone)]
+pub struct AovBuffers {
+    pub buffers: Vec<(Aov, Vector2D<Color>)>
+}
+
+impl AovBuffers {
+    pub fn get(&self, aov: Aov) -> Option<&Vector2D<Color>> {
+        self.buffers.iter().find(|(buffer_aov, _)| *buffer_aov == aov).map(|(_, buffer)| buffer)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&Vector2D<Color>> {
+        self.buffers.iter().find(|(aov, _)| aov.name() == name).map(|(_, buffer)| buffer)
+    }
+
+    // The format follows the extension: .exr (float), .pfm or .png (16 bit, clamped to [0, 1])
+    pub fn save(&self, aov: Aov, filename: &str) -> Result<(), RTracerError> {
+        let buffer: &Vector2D<Color> = self.get(aov)
+            .ok_or_else(|| RTracerError::image_write(filename, format!("the {} AOV was not rendered", aov.name())))?;
+        let extension: String = filename.rsplit('.').next().unwrap_or("").to_lowercase();
+        match extension.as_str() {
+            "exr" => save_vector2d_as_exr(buffer, filename, ExrPrecision::Float, PostProcessing::None),
+            "pfm" => save_vector2d_as_pfm(buffer, filename, PostProcessing::None),
+            "png" => save_vector2d_as_png16(buffer, filename, PostProcessing::None),
+            _ => Err(RTracerError::UnsupportedFormat { path: filename.to_string() })
+        }
+    }
+
+    // Writes every buffer as `<prefix>_<name>.exr`
+    pub fn save_all(&self, prefix: &str) -> Result<(), RTracerError> {
+        for (aov, _) in &self.buffers {
+            self.save(*aov, &format!("{}_{}.exr", prefix, aov.name()))?;
+        }
+        Ok(())
+    }
+}
+
+// Running totals for one pixel. Continuous values are averaged over the samples that hit something,
+// ids take the value hit most often so they never blend into ids that do not exist.
+#[derive(Clone)]
+pub(crate) struct AovAccumulator {
+    hits: u32,
+    depth: f64,
+    normal: Vector3,
+    albedo: Color,
+    position: Vector3,
+    uv: Vector2,
+    material_ids: Vec<(usize, u32)>,
+    object_ids: Vec<(usize, u32)>
+}
+
+impl AovAccumulator {
+    pub(crate) fn new() -> AovAccumulator {
+        AovAccumulator {
+            hits: 0,
+            depth: 0.0,
+            normal: Vector3::zero(),
+            albedo: Color::black(),
+            position: Vector3::zero(),
+            uv: Vector2::zero(),
+            material_ids: vec![],
+            object_ids: vec![]
+        }
+    }
+
+    pub(crate) fn add_sample(&mut self, hit: &HitPoint, scene: &Scene, depth: f64) {
+        if hit.is_empty {
+            return
+        }
+        let (diffuse_color, _, _, _, normal_map_vector, _, _) = Ray::get_maps(hit, scene);
+
+        self.hits += 1;
+        self.depth += depth;
+        self.normal += Ray::shading_normal(hit, normal_map_vector);
+        self.albedo += diffuse_color;
+        self.position += hit.point;
+        self.uv = self.uv + hit.barycentric_coords.x*hit.object.p1_texture
+            + hit.barycentric_coords.y*hit.object.p2_texture
+            + hit.barycentric_coords.z*hit.object.p3_texture;
+        Self::count(&mut self.material_ids, hit.object.material_id);
+        Self::count(&mut self.object_ids, hit.object.object_id);
+    }
+
+    pub(crate) fn resolve(&self, aov: Aov) -> Color {
+        if self.hits == 0 {
+            return match aov {
+                Aov::Depth => Color::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                _ if aov.is_id() => Color::new(-1.0, -1.0, -1.0),
+                _ => Color::black()
+            }
+        }
+
+        let hits: f64 = self.hits as f64;
+        let from_vector = |v: Vector3| -> Color { Color::new(v.x, v.y, v.z) };
+        match aov {
+            Aov::Depth => Color::new(self.depth / hits, self.depth / hits, self.depth / hits),
+            Aov::Normal => from_vector(self.normal.normalize()),
+            Aov::Albedo => self.albedo * (1.0 / hits),
+            Aov::Position => from_vector(self.position / hits),
+            Aov::Uv => Color::new(self.uv.x / hits, self.uv.y / hits, 0.0),
+            Aov::MaterialId => Self::id_color(&self.material_ids),
+            Aov::ObjectId => Self::id_color(&self.object_ids)
+        }
+    }
+
+    fn count(counts: &mut Vec<(usize, u32)>, id: usize) {
+        match counts.iter_mut().find(|(counted_id, _)| *counted_id == id) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((id, 1))
+        }
+    }
+
+    fn id_color(counts: &[(usize, u32)]) -> Color {
+        let id: f64 = counts.iter().max_by_key(|(_, count)| *count).map_or(-1.0, |(id, _)| *id as f64);
+        Color::new(id, id, id)
+    }
+}
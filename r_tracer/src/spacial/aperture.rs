@@ -0,0 +1,128 @@
+use std::f64::consts::PI;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::vector2d::Vector2D;
+use crate::datatypes::color::Color;
+use crate::datatypes::material::check_positive;
+use crate::utilities::file_utilities::import_texture;
+use crate::utilities::sampling;
+use crate::error::RTracerError;
+
+
+// Shape of the lens opening, which out of focus highlights take on
+#[derive(Clone)]
+pub enum ApertureShape {
+    Disk,
+    // Regular polygon of `blades` sides, turned by `rotation` degrees
+    Polygon { blades: u32, rotation: f64 },
+    Image(ApertureImage)
+}
+
+#[derive(Clone)]
+pub struct Aperture {
+    pub shape: ApertureShape,
+    // How many times taller than wide the aperture is, 2 gives the oval highlights of a 2x anamorphic lens
+    pub squeeze: f64
+}
+
+impl Aperture {
+    pub fn disk() -> Aperture {
+        Aperture { shape: ApertureShape::Disk, squeeze: 1.0 }
+    }
+
+    pub fn polygon(blades: u32, rotation: f64) -> Aperture {
+        Aperture { shape: ApertureShape::Polygon { blades, rotation }, squeeze: 1.0 }
+    }
+
+    // Brighter pixels of `image` let more light through, black ones none
+    pub fn from_image(image: &Vector2D<Color>) -> Result<Aperture, RTracerError> {
+        Ok(Aperture { shape: ApertureShape::Image(ApertureImage::new(image)?), squeeze: 1.0 })
+    }
+
+    pub fn from_file(path: &str) -> Result<Aperture, RTracerError> {
+        Self::from_image(&import_texture(path)?)
+    }
+
+    pub fn with_squeeze(mut self, squeeze: f64) -> Aperture {
+        self.squeeze = squeeze;
+        self
+    }
+
+    // Point on the aperture scaled to a radius of 1, as (0, right, up) components like Vector3::random_perturb
+    pub fn sample(&self) -> Vector3 {
+        let point: Vector3 = match &self.shape {
+            ApertureShape::Disk => Vector3::random_perturb(Vector2::new(1.0, 1.0)),
+            ApertureShape::Polygon { blades, rotation } => Self::sample_polygon(*blades, *rotation),
+            ApertureShape::Image(image) => image.sample()
+        };
+        if self.squeeze == 1.0 {
+            return point
+        }
+        Vector3::new(0.0, point.y / self.squeeze, point.z)
+    }
+
+    // Uniform over the polygon: a random one of its equal triangles around the center, then a uniform point in it
+    fn sample_polygon(blades: u32, rotation: f64) -> Vector3 {
+        let step: f64 = 2.0 * PI / blades as f64;
+        let corner: f64 = (sampling::random() * blades as f64).floor().min(blades as f64 - 1.0) * step + rotation.to_radians();
+        let (mut a, mut b) = (sampling::random(), sampling::random());
+        if a + b > 1.0 {
+            (a, b) = (1.0 - a, 1.0 - b);
+        }
+        Vector3::new(
+            0.0,
+            a * corner.sin() + b * (corner + step).sin(),
+            a * corner.cos() + b * (corner + step).cos()
+        )
+    }
+
+    pub fn validate(&self) -> Result<(), RTracerError> {
+        check_positive("aperture squeeze", self.squeeze)?;
+        if let ApertureShape::Polygon { blades, rotation } = self.shape {
+            if blades < 3 {
+                return Err(RTracerError::invalid_parameter("aperture blades", format!("must be at least 3, got {}", blades)))
+            }
+            if !rotation.is_finite() {
+                return Err(RTracerError::invalid_parameter("aperture rotation", "must be finite"))
+            }
+        }
+        Ok(())
+    }
+}
+
+// Aperture mask importance sampled through the cumulative brightness of its pixels
+#[derive(Clone)]
+pub struct ApertureImage {
+    pub width: usize,
+    pub height: usize,
+    cdf: Vec<f64>
+}
+
+impl ApertureImage {
+    pub fn new(image: &Vector2D<Color>) -> Result<ApertureImage, RTracerError> {
+        let mut total: f64 = 0.0;
+        let mut cdf: Vec<f64> = Vec::with_capacity(image.data.len());
+        for color in &image.data {
+            total += color.to_greyscale().max(0.0);
+            cdf.push(total);
+        }
+        if !(total > 0.0 && total.is_finite()) {
+            return Err(RTracerError::invalid_parameter("aperture image", "has no bright pixels to let light through"))
+        }
+        for value in cdf.iter_mut() {
+            *value /= total;
+        }
+        Ok(ApertureImage { width: image.width, height: image.height, cdf })
+    }
+
+    // The image is fit into the unit circle's bounding square, keeping its aspect ratio, top row up
+    fn sample(&self) -> Vector3 {
+        let target: f64 = sampling::random();
+        let index: usize = self.cdf.partition_point(|&value: &f64| value <= target).min(self.cdf.len() - 1);
+        let (row, col) = (index / self.width, index % self.width);
+        let size: f64 = usize::max(self.width, self.height) as f64;
+        let right: f64 = (2.0 * (col as f64 + sampling::random()) - self.width as f64) / size;
+        let up: f64 = (self.height as f64 - 2.0 * (row as f64 + sampling::random())) / size;
+        Vector3::new(0.0, right, up)
+    }
+}
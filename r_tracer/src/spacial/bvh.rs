@@ -1,5 +1,6 @@
 use crate::spacial::tri::Tri;
 use crate::spacial::mesh_object::MeshObject;
+use crate::datatypes::material::Material;
 use crate::datatypes::vector3::Vector3;
 use std::cmp::Ordering;
 use rand::Rng;
@@ -18,10 +19,19 @@ pub struct BVH {
 
 impl BVH {
     pub fn new(mesh_objects: &[MeshObject]) -> BVH {
+        let materials: Vec<Material> = MeshObject::unique_materials(mesh_objects);
         let mut tris: Vec<Tri> = vec![];
-        for mesh in mesh_objects {
+        let mut material_id: usize = 0;
+        for (object_id, mesh) in mesh_objects.iter().enumerate() {
             for m in &mesh.tris {
-                tris.push(*m);
+                let mut tri: Tri = *m;
+                // Neighbouring triangles nearly always share a material, only search on a change
+                if materials[material_id] != tri.material {
+                    material_id = materials.iter().position(|material: &Material| *material == tri.material).unwrap_or(0);
+                }
+                tri.object_id = object_id;
+                tri.material_id = material_id;
+                tris.push(tri);
             }
         }
         let start_time = Instant::now();
@@ -4,14 +4,82 @@ use crate::datatypes::color::Color;
 use crate::datatypes::vector2d::Vector2D;
 use crate::utilities::frame_handler::FrameHandler;
 use crate::utilities::postprocessing::remove_fireflies;
+use crate::utilities::scene_file::{load_scene_with_camera, SceneFileError};
+use crate::utilities::mesh_cache::{MeshCache, fnv1a, FNV_OFFSET};
+use crate::utilities::render_metadata::RenderMetadata;
+use crate::utilities::checkpoint::{Checkpoint, CheckpointSettings};
+use crate::utilities::sampling;
+use crate::error::RTracerError;
 use crate::spacial::scene::Scene;
+use crate::spacial::camera_builder::CameraBuilder;
+use crate::spacial::camera_basis::CameraBasis;
+use crate::spacial::physical_camera::PhysicalCamera;
+use crate::spacial::aperture::{Aperture, ApertureShape};
+use crate::spacial::stereo::{Stereo, Eye, StereoPair};
+use crate::spacial::crop_region::CropRegion;
 use crate::spacial::ray::Ray;
 use crate::spacial::bvh::BVH;
+use crate::spacial::tri::Tri;
+use crate::spacial::aov::{Aov, AovBuffers, AovAccumulator};
+use crate::spacial::cryptomatte::{IdMatte, MatteKind, CoverageRanks};
+use crate::spacial::mesh_object::MeshObject;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::hit_point::HitPoint;
 use rayon::prelude::*;
 use std::sync::{Mutex, MutexGuard, Arc};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use std::f64::consts::PI;
 
 
+// Angle the image spans, in degrees
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FieldOfView {
+    Horizontal(f64),
+    Vertical(f64)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // Parallel rays over an image plane `scale` world units across its wider side
+    Orthographic { scale: f64 },
+    // The whole sphere, laid out like an environment map: with the default orientation a render can be used
+    // as the environment map of another scene
+    Equirectangular,
+    // Circular fisheye, `fov` degrees across an image circle that fills the shorter side of the image
+    Fisheye { mapping: FisheyeMapping, fov: f64 }
+}
+
+// How a fisheye spreads angles from the view axis over the image circle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    // Distance from the center proportional to the angle
+    Equidistant,
+    // Equal solid angles cover equal areas of the image
+    Equisolid
+}
+
+// Where the camera is at the end of the frame. It moves in a straight line from its start position and turns
+// evenly towards this orientation while the shutter is open.
+#[derive(Copy, Clone, PartialEq)]
+pub struct CameraMotion {
+    pub position: Vector3,
+    pub basis: CameraBasis
+}
+
+impl CameraMotion {
+    // Euler angles in degrees, like Camera::rotation
+    pub fn new(position: Vector3, rotation: Vector3) -> CameraMotion {
+        CameraMotion { position, basis: CameraBasis::from_rotation(rotation) }
+    }
+
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3) -> Result<CameraMotion, RTracerError> {
+        Ok(CameraMotion { position: eye, basis: CameraBasis::look_at(eye, target, up)? })
+    }
+}
+
 #[derive(Clone)]
 pub struct Camera {
     pub position: Vector3,
@@ -26,7 +94,33 @@ pub struct Camera {
     pub dof_strength: f64,
     pub focal_distance: f64,
     pub fov: f64,
-    pub tile_size: usize
+    pub tile_size: usize,
+    pub aovs: Vec<Aov>,
+    // Where the BVH is cached between runs, built from scratch every time when None
+    pub cache: Option<MeshCache>,
+    // Picks the random numbers of every sample, the same seed renders the same image
+    pub seed: u64,
+    pub checkpoint: Option<CheckpointSettings>,
+    // Set by look_at, takes the place of `rotation` when present
+    pub basis: Option<CameraBasis>,
+    // Takes the place of `fov` when present
+    pub field_of_view: Option<FieldOfView>,
+    pub projection: Projection,
+    // Takes the place of `fov`, `field_of_view` and `dof_strength` when present, and scales the image brightness
+    pub physical: Option<PhysicalCamera>,
+    // Shape of the lens, scaled by lens_radius
+    pub aperture: Aperture,
+    // When the shutter opens and closes, as times between 0 (the start of the frame) and 1 (its end). Primary
+    // rays are spread evenly over the interval, the default of both 0 renders the start of the frame.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // The camera stays at its start position when None
+    pub motion: Option<CameraMotion>,
+    pub stereo: Option<Stereo>,
+    // Which eye of `stereo` is rendered, the point halfway between them when None
+    pub eye: Option<Eye>,
+    // Only these pixels are rendered, the others keep whatever the image being rendered into holds
+    pub crop: Option<CropRegion>
 }
 
 impl Camera {
@@ -49,89 +143,517 @@ impl Camera {
              dof_strength: dof_strength,
              focal_distance: focal_distance,
              fov: fov,
-             tile_size: tile_size
+             tile_size: tile_size,
+             aovs: vec![],
+             cache: None,
+             seed: 0,
+             checkpoint: None,
+             basis: None,
+             field_of_view: None,
+             projection: Projection::Perspective,
+             physical: None,
+             aperture: Aperture::disk(),
+             shutter_open: 0.0,
+             shutter_close: 0.0,
+             motion: None,
+             stereo: None,
+             eye: None,
+             crop: None
+        }
+    }
+
+    // Camera at `eye` looking at `target`, with the defaults of Camera::builder
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3, scene: Scene,
+        width: usize, height: usize) -> Result<Camera, RTracerError> {
+        CameraBuilder::new(scene, width, height).with_look_at(eye, target, up).build()
+    }
+
+    pub fn builder(scene: Scene, width: usize, height: usize) -> CameraBuilder {
+        CameraBuilder::new(scene, width, height)
+    }
+
+    pub fn with_aovs(mut self, aovs: Vec<Aov>) -> Camera {
+        self.aovs = aovs;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: MeshCache) -> Camera {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Camera {
+        self.seed = seed;
+        self
+    }
+
+    // Writes the accumulated samples to `path` every `interval` and once the render finishes
+    pub fn with_checkpoint(mut self, path: &str, interval: Duration) -> Camera {
+        self.checkpoint = Some(CheckpointSettings::new(path, interval));
+        self
+    }
+
+    pub fn with_horizontal_fov(mut self, degrees: f64) -> Camera {
+        self.field_of_view = Some(FieldOfView::Horizontal(degrees));
+        self
+    }
+
+    pub fn with_vertical_fov(mut self, degrees: f64) -> Camera {
+        self.field_of_view = Some(FieldOfView::Vertical(degrees));
+        self
+    }
+
+    pub fn with_orthographic(mut self, scale: f64) -> Camera {
+        self.projection = Projection::Orthographic { scale };
+        self
+    }
+
+    pub fn with_equirectangular(mut self) -> Camera {
+        self.projection = Projection::Equirectangular;
+        self
+    }
+
+    pub fn with_fisheye(mut self, mapping: FisheyeMapping, fov: f64) -> Camera {
+        self.projection = Projection::Fisheye { mapping, fov };
+        self
+    }
+
+    pub fn with_physical(mut self, physical: PhysicalCamera) -> Camera {
+        self.physical = Some(physical);
+        self
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> Camera {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Camera {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    pub fn with_motion(mut self, motion: CameraMotion) -> Camera {
+        self.motion = Some(motion);
+        self
+    }
+
+    pub fn with_stereo(mut self, stereo: Stereo) -> Camera {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    pub fn with_crop(mut self, crop: CropRegion) -> Camera {
+        self.crop = Some(crop);
+        self
+    }
+
+    // The pixels a render covers, the whole frame without a crop
+    pub fn region(&self) -> CropRegion {
+        self.crop.unwrap_or_else(|| CropRegion::full(self.width, self.height))
+    }
+
+    pub fn basis(&self) -> CameraBasis {
+        self.basis.unwrap_or_else(|| CameraBasis::from_rotation(self.rotation))
+    }
+
+    // Distance from the camera to the image plane, in pixels
+    pub fn image_plane_distance(&self) -> f64 {
+        let field_of_view: Option<FieldOfView> = match self.physical {
+            Some(physical) => Some(physical.field_of_view(self.width, self.height)),
+            None => self.field_of_view
+        };
+        match field_of_view {
+            None => (self.width as f64) / self.fov,
+            Some(FieldOfView::Horizontal(degrees)) => (self.width as f64) / 2.0 / (degrees.to_radians() / 2.0).tan(),
+            Some(FieldOfView::Vertical(degrees)) => (self.height as f64) / 2.0 / (degrees.to_radians() / 2.0).tan()
+        }
+    }
+
+    // Radius of the disk primary rays start from, in world units
+    pub fn lens_radius(&self) -> f64 {
+        self.physical.map_or(self.dof_strength, |physical: PhysicalCamera| physical.aperture_radius())
+    }
+
+    // Factor from scene radiance to pixel values
+    pub fn exposure_scale(&self) -> f64 {
+        self.physical.map_or(1.0, |physical: PhysicalCamera| physical.exposure_scale())
+    }
+
+    // Renderer space direction of `local`, given as components along the forward, right and up axes of the camera
+    pub fn to_world(&self, local: Vector3) -> Vector3 {
+        match self.basis {
+            Some(basis) => basis.to_world(local),
+            None => local.rot(self.rotation)
+        }
+    }
+
+    // Signed distance from the camera position to the eye being rendered, along the right axis
+    pub fn eye_offset(&self) -> f64 {
+        match (self.stereo, self.eye) {
+            (Some(stereo), Some(eye)) => stereo.eye_offset(eye),
+            _ => 0.0
+        }
+    }
+
+    // Random time while the shutter is open
+    pub fn sample_time(&self) -> f64 {
+        if self.shutter_close > self.shutter_open {
+            return sampling::random_range(self.shutter_open..self.shutter_close)
+        }
+        self.shutter_open
+    }
+
+    // Position and orientation of the camera at `time`
+    pub fn pose_at(&self, time: f64) -> (Vector3, CameraBasis) {
+        match self.motion {
+            Some(motion) => (Vector3::lerp(self.position, motion.position, time), self.basis().lerp(&motion.basis, time)),
+            None => (self.position, self.basis())
+        }
+    }
+
+    // Carries a primary ray set up from the start pose along with the camera to where it is at the ray's time
+    pub fn move_to_time(&self, ray: Ray) -> Ray {
+        if self.motion.is_none() {
+            return ray
+        }
+        let start: CameraBasis = self.basis();
+        let (position, basis) = self.pose_at(ray.time);
+        Ray::new(
+            position + basis.to_world(start.to_local(ray.origin - self.position)),
+            basis.to_world(start.to_local(ray.direction))
+        ).with_time(ray.time)
+    }
+
+    // Where the primary ray through the image plane point `right`, `up` pixels from its center starts,
+    // before depth of field moves it across the lens
+    pub fn view_origin(&self, right: f64, up: f64) -> Vector3 {
+        let basis: CameraBasis = self.basis();
+        let eye: Vector3 = match self.projection {
+            // Each viewing direction gets its own eye position, side by side across it, so the whole panorama
+            // is in stereo (omni-directional stereo)
+            Projection::Equirectangular => {
+                let azimuth: f64 = self.equirectangular_angles(right, up).0;
+                self.eye_offset() * self.to_world(Vector3::new(-azimuth.cos(), azimuth.sin(), 0.0))
+            },
+            _ => self.eye_offset() * basis.right
+        };
+        match self.projection {
+            Projection::Orthographic { scale } => {
+                let world_per_pixel: f64 = scale / usize::max(self.width, self.height) as f64;
+                self.position + eye + (right * world_per_pixel) * basis.right + (up * world_per_pixel) * basis.up
+            },
+            _ => self.position + eye
+        }
+    }
+
+    // Azimuth and elevation, in radians, of the equirectangular view through the image plane point `right`, `up`
+    fn equirectangular_angles(&self, right: f64, up: f64) -> (f64, f64) {
+        // Inverse of Ray::get_environment_color, through the middle of the pixels rather than their
+        // corners so every pixel lands on the same pixel of an environment map of the same size
+        (2.0 * PI * ((right + 0.5) / self.width as f64 + 0.5), PI * (up - 0.5) / self.height as f64)
+    }
+
+    // Unit direction of the primary ray through the image plane point `right`, `up` pixels from its center.
+    // Zero outside the image circle of a fisheye, where no ray is cast.
+    pub fn view_direction(&self, right: f64, up: f64) -> Vector3 {
+        // Components along the forward, right and up axes of the camera
+        let local: Vector3 = match self.projection {
+            Projection::Perspective => {
+                // Off-axis stereo: the eye's image plane slides against it, so it still meets the middle of the
+                // view at the convergence distance
+                let distance: f64 = self.image_plane_distance();
+                let shift: f64 = self.stereo.map_or(0.0, |stereo: Stereo| self.eye_offset() * distance / stereo.convergence_distance);
+                Vector3::new(distance, right - shift, up).normalize()
+            },
+            Projection::Orthographic { .. } => Vector3::new(1.0, 0.0, 0.0),
+            Projection::Equirectangular => {
+                let (azimuth, elevation) = self.equirectangular_angles(right, up);
+                Vector3::new(elevation.cos() * azimuth.sin(), elevation.cos() * azimuth.cos(), elevation.sin())
+            },
+            Projection::Fisheye { mapping, fov } => {
+                let radius: f64 = (right * right + up * up).sqrt() / (usize::min(self.width, self.height) as f64 / 2.0);
+                if radius > 1.0 {
+                    return Vector3::zero()
+                }
+                let half_fov: f64 = fov.to_radians() / 2.0;
+                let angle: f64 = match mapping {
+                    FisheyeMapping::Equidistant => radius * half_fov,
+                    FisheyeMapping::Equisolid => 2.0 * (radius * (half_fov / 2.0).sin()).asin()
+                };
+                let around: f64 = up.atan2(right);
+                Vector3::new(angle.cos(), angle.sin() * around.cos(), angle.sin() * around.sin())
+            }
+        };
+        self.to_world(local)
+    }
+
+    pub fn from_file(path: &str) -> Result<Camera, SceneFileError> {
+        load_scene_with_camera(path)
+    }
+
+    pub fn render_scene(self, handler: FrameHandler, sample_count: u32) -> Vector2D<Color> {
+        self.render_partial(handler, sample_count).accumulation
+    }
+
+    // Same as render_scene, along with a record of how the image was made for the metadata of the saved file
+    pub fn render_scene_with_metadata(self, handler: FrameHandler, sample_count: u32) -> (Vector2D<Color>, RenderMetadata) {
+        let start_time: Instant = Instant::now();
+        let mut metadata: RenderMetadata = RenderMetadata::new(&self, sample_count, Duration::ZERO);
+        let frame: Vector2D<Color> = self.render_scene(handler, sample_count);
+        metadata.render_time = start_time.elapsed();
+        (frame, metadata)
+    }
+
+    // Same as render_scene, keeping the sample count of every pixel so renders made with different seeds
+    // (on other machines, say) can be combined with Checkpoint::merge
+    pub fn render_partial(self, mut handler: FrameHandler, sample_count: u32) -> Checkpoint {
+        let bvh: BVH = self.build_bvh();
+        self.render_beauty(&mut handler, sample_count, &bvh, Checkpoint::for_camera(&self))
+    }
+
+    // Carries on with the render saved in a checkpoint until every pixel has `sample_count` samples
+    pub fn resume_scene(mut self, mut handler: FrameHandler, sample_count: u32, checkpoint_path: &str) -> Result<Vector2D<Color>, RTracerError> {
+        let progress: Checkpoint = Checkpoint::load(checkpoint_path)?;
+        if (progress.width(), progress.height()) != (self.width, self.height) {
+            return Err(RTracerError::checkpoint(checkpoint_path, format!("checkpoint is {}x{} but the camera renders {}x{}",
+                progress.width(), progress.height(), self.width, self.height)))
+        }
+        if progress.camera_hash != self.fingerprint() {
+            return Err(RTracerError::checkpoint(checkpoint_path, "rendered with different camera or scene settings"))
+        }
+        println!("Resuming from {} samples: {}", progress.min_samples(), checkpoint_path);
+        self.seed = progress.seed;
+        let bvh: BVH = self.build_bvh();
+        Ok(self.render_beauty(&mut handler, sample_count, &bvh, progress).accumulation)
+    }
+
+    // Renders the beauty pass along with the AOVs listed in `self.aovs`, sharing one BVH
+    pub fn render_scene_with_aovs(self, mut handler: FrameHandler, sample_count: u32) -> (Vector2D<Color>, AovBuffers) {
+        let bvh: BVH = self.build_bvh();
+        let aovs: AovBuffers = self.render_aovs(&bvh, sample_count);
+        let progress: Checkpoint = Checkpoint::for_camera(&self);
+        (self.render_beauty(&mut handler, sample_count, &bvh, progress).accumulation, aovs)
+    }
+
+    // Renders the left and then the right eye of `stereo` through the same BVH. Each eye keeps its own
+    // checkpoint, named after the eye; to resume one, set `eye` and call resume_scene with its file.
+    pub fn render_stereo(mut self, mut handler: FrameHandler, sample_count: u32) -> Result<StereoPair, RTracerError> {
+        if self.stereo.is_none() {
+            return Err(RTracerError::invalid_parameter("stereo", "the camera has no stereo settings"))
         }
+        let bvh: BVH = self.build_bvh();
+        let checkpoint: Option<CheckpointSettings> = self.checkpoint.clone();
+        let mut render_eye = |eye: Eye| -> Vector2D<Color> {
+            self.eye = Some(eye);
+            self.checkpoint = checkpoint.as_ref()
+                .map(|settings: &CheckpointSettings| CheckpointSettings::new(&eye.path(&settings.path), settings.interval));
+            self.render_beauty(&mut handler, sample_count, &bvh, Checkpoint::for_camera(&self)).accumulation
+        };
+        Ok(StereoPair { left: render_eye(Eye::Left), right: render_eye(Eye::Right) })
     }
 
-    pub fn render_scene(self, mut handler: FrameHandler, sample_count: u32) -> Vector2D<Color> {
+    // Renders only `region` of the frame, at full resolution, and returns it as an image of its own
+    pub fn render_crop(mut self, handler: FrameHandler, sample_count: u32, region: CropRegion) -> Result<Vector2D<Color>, RTracerError> {
+        region.validate(self.width, self.height)?;
+        self.crop = Some(region);
+        Ok(region.crop(&self.render_scene(handler, sample_count)))
+    }
 
-        let bvh: BVH = BVH::new(&self.scene.meshes);
+    // Re-renders `region` of a full size `image`, such as an earlier render of the same shot, in place
+    pub fn render_crop_into(mut self, mut handler: FrameHandler, sample_count: u32, region: CropRegion,
+        image: &mut Vector2D<Color>) -> Result<(), RTracerError> {
+        region.validate(self.width, self.height)?;
+        if (image.width, image.height) != (self.width, self.height) {
+            return Err(RTracerError::invalid_parameter("crop image",
+                format!("is {}x{} but the camera renders {}x{}", image.width, image.height, self.width, self.height)))
+        }
+        self.crop = Some(region);
+        let bvh: BVH = self.build_bvh();
+        let mut progress: Checkpoint = Checkpoint::for_camera(&self);
+        progress.accumulation = image.clone();
+        *image = self.render_beauty(&mut handler, sample_count, &bvh, progress).accumulation;
+        Ok(())
+    }
+
+    // Cheap hash of the camera settings and the layout of the scene (mesh sizes and first vertices), enough to
+    // catch renders of different shots. Tile size, seed and sample count are left out, renders that only differ
+    // in those can be resumed or merged.
+    pub fn fingerprint(&self) -> u64 {
+        let mut values: Vec<f64> = vec![
+            self.position.x, self.position.y, self.position.z,
+            self.rotation.x, self.rotation.y, self.rotation.z,
+            self.exposure, self.width as f64, self.height as f64, self.max_bounces as f64, self.rays_per_pixel as f64,
+            self.blur_strength, self.dof_strength, self.focal_distance, self.fov,
+            self.scene.env_color.red, self.scene.env_color.green, self.scene.env_color.blue,
+            self.scene.environment_intensity, self.scene.environment_rotation,
+            self.scene.meshes.len() as f64, self.scene.texture_maps.len() as f64
+        ];
+        if let Some(basis) = self.basis {
+            values.extend([basis.forward.x, basis.forward.y, basis.forward.z, basis.up.x, basis.up.y, basis.up.z]);
+        }
+        match self.field_of_view {
+            Some(FieldOfView::Horizontal(degrees)) => values.extend([1.0, degrees]),
+            Some(FieldOfView::Vertical(degrees)) => values.extend([2.0, degrees]),
+            None => ()
+        }
+        match self.projection {
+            Projection::Perspective => (),
+            Projection::Orthographic { scale } => values.extend([3.0, scale]),
+            Projection::Equirectangular => values.push(4.0),
+            Projection::Fisheye { mapping: FisheyeMapping::Equidistant, fov } => values.extend([5.0, fov]),
+            Projection::Fisheye { mapping: FisheyeMapping::Equisolid, fov } => values.extend([6.0, fov])
+        }
+        if let Some(physical) = self.physical {
+            values.extend([7.0, physical.sensor_width, physical.sensor_height, physical.focal_length, physical.f_number,
+                physical.shutter_time, physical.iso, physical.units_per_meter]);
+        }
+        match &self.aperture.shape {
+            ApertureShape::Disk => (),
+            ApertureShape::Polygon { blades, rotation } => values.extend([8.0, *blades as f64, *rotation]),
+            ApertureShape::Image(image) => values.extend([9.0, image.width as f64, image.height as f64])
+        }
+        if self.aperture.squeeze != 1.0 {
+            values.extend([10.0, self.aperture.squeeze]);
+        }
+        if self.shutter_open != 0.0 || self.shutter_close != 0.0 {
+            values.extend([11.0, self.shutter_open, self.shutter_close]);
+        }
+        if let Some(motion) = self.motion {
+            values.extend([12.0, motion.position.x, motion.position.y, motion.position.z,
+                motion.basis.forward.x, motion.basis.forward.y, motion.basis.forward.z,
+                motion.basis.up.x, motion.basis.up.y, motion.basis.up.z]);
+        }
+        if let (Some(stereo), Some(_)) = (self.stereo, self.eye) {
+            values.extend([14.0, self.eye_offset(), stereo.convergence_distance]);
+        }
+        for mesh in &self.scene.meshes {
+            values.push(mesh.tris.len() as f64);
+            if let Some(tri) = mesh.tris.first() {
+                values.extend([tri.p1.x, tri.p1.y, tri.p1.z]);
+                if let Some(motion) = tri.motion {
+                    values.extend([13.0, motion.translation.x, motion.translation.y, motion.translation.z,
+                        motion.rotation.x, motion.rotation.y, motion.rotation.z, motion.pivot.x, motion.pivot.y, motion.pivot.z]);
+                }
+            }
+        }
+        let bytes: Vec<u8> = values.iter().flat_map(|value: &f64| value.to_le_bytes()).collect();
+        fnv1a(FNV_OFFSET, &bytes)
+    }
+
+    // Goes through the cache when one is set
+    pub fn build_bvh(&self) -> BVH {
+        match &self.cache {
+            Some(cache) => cache.load_bvh(&self.scene.meshes),
+            None => BVH::new(&self.scene.meshes)
+        }
+    }
+
+    fn render_beauty(&self, handler: &mut FrameHandler, sample_count: u32, bvh: &BVH, progress: Checkpoint) -> Checkpoint {
         let height: usize = self.height;
         let width: usize = self.width;
         let tile_size: usize = self.tile_size;
-        let frame_out: Vector2D<Color>;
-        
+        let environment_map: Option<&Vector2D<Color>> = self.scene.environment_map.as_ref();
+        let mut progress: Checkpoint = progress;
+
         if self.tile_size > 0 {
-            let tiles: Vec<(usize, usize)> = Self::get_tiles(width, height, tile_size);
+            let region: CropRegion = self.region();
+            let tiles: Vec<(usize, usize)> = Self::get_tiles(&region, tile_size);
             let tile_slice: &[(usize, usize)] = &tiles;
-            let frame: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
 
-            let frame: Mutex<Vector2D<Color>> = Mutex::new(frame);
+            let shared_progress: Mutex<Checkpoint> = Mutex::new(progress);
+            let last_save: Mutex<Instant> = Mutex::new(Instant::now());
             let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
             let total_tiles = tile_slice.len();
             
             tile_slice.par_iter().for_each(|&t| {
-                let tile_render = self.render_tile(
-                    t.0,
-                    usize::min(width, t.0 + tile_size),
-                    t.1,
-                    usize::min(height, t.1 + tile_size),
-                    sample_count as usize,
-                    &bvh,
-                    (0..sample_count).map(|s| 1.0 / (s as f64 + 1.0)).collect()
+                self.render_tile_samples(
+                    t.1..usize::min(region.rows().end, t.1 + tile_size),
+                    t.0..usize::min(region.columns().end, t.0 + tile_size),
+                    sample_count,
+                    bvh,
+                    environment_map,
+                    &shared_progress
                 );
             
-                let mut frame: MutexGuard<Vector2D<Color>> = frame.lock().unwrap();
-                for x in 0..tile_render.width {
-                    for y in 0..tile_render.height {
-                        frame.set(
-                            usize::min(height, t.1 + x),
-                            usize::min(width, t.0 + y),
-                            *tile_render.get(x, y),
-                        );
-                    }
-                }
-            
                 let current_tile: usize = counter.fetch_add(1, Ordering::Relaxed);
                 println!("Render progress: {}%", (100.0 * ((current_tile + 1) as f64) / (total_tiles as f64)) as usize);
+
+                let mut last_save: MutexGuard<Instant> = last_save.lock().unwrap();
+                self.save_checkpoint_if_due(&shared_progress.lock().unwrap(), &mut last_save);
             });
             
-            let frame: MutexGuard<Vector2D<Color>> = frame.lock().unwrap();
-            let converted_values: Vec<u32> = frame.data.iter()
+            progress = shared_progress.into_inner().unwrap();
+            let converted_values: Vec<u32> = progress.accumulation.data.iter()
             .map(|color| color.as_buffer_color()).collect();
             
             let _update: Result<(), Error> = handler.window.update_with_buffer(
-                &converted_values.clone(), width, height
+                &converted_values, width, height
             );
-            frame_out = frame.clone();
         } else {
-            let mut new_render: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
-            let mut old_render: Vector2D<Color> = new_render;
-            let mut pixel_accumulation: Vector2D<Color> = old_render;
-            let weight_slice: Vec<f64> = (0..sample_count).map(|s| 1.0 / (s as f64 + 1.0)).collect();
-
-            let sample_count_usize: usize = sample_count as usize;
-            for i in 0..sample_count_usize {
-
-                println!("Sample {}/{}", i + 1, sample_count);
-
-                old_render = pixel_accumulation;
-                new_render = self.render_whole_sample(&bvh);
-                old_render *= 1.0 - weight_slice[i];
-                new_render *= weight_slice[i];
-                pixel_accumulation = old_render + new_render;            
-                
-                let converted_values: Vec<u32> = remove_fireflies(&pixel_accumulation).data.iter()
+            self.render_progressive(bvh, &mut progress, sample_count, |progress: &Checkpoint| {
+                let converted_values: Vec<u32> = remove_fireflies(&progress.accumulation).data.iter()
                     .map(|color| color.as_buffer_color()).collect();
 
                 let _update: Result<(), Error> = handler.window.update_with_buffer(
                     &converted_values, width, height
                 );
+            });
+        }
+
+        if self.checkpoint.is_some() {
+            self.save_checkpoint(&progress);
+        }
+        progress
+    }
+
+    // Adds whole frame samples until every pixel holds `sample_count` of them, calling `on_sample` after each.
+    // Needs no window, so partial renders can also be produced headless and merged or resumed later. With a
+    // crop only the pixels inside it are sampled.
+    pub fn render_progressive(&self, bvh: &BVH, progress: &mut Checkpoint, sample_count: u32,
+        mut on_sample: impl FnMut(&Checkpoint)) {
+        let mut last_save: Instant = Instant::now();
+        let region: CropRegion = self.region();
+        let first_sample: u32 = region.indices(self.width).map(|index: usize| progress.sample_counts[index]).min().unwrap_or(0);
+        for i in first_sample..sample_count {
+            println!("Sample {}/{}", i + 1, sample_count);
+
+            let frame: Vector2D<Color> = self.render_sample(bvh, i as u64);
+            for index in region.indices(self.width) {
+                // Pixels of a resumed tiled render may already be further along
+                if progress.sample_counts[index] == i {
+                    progress.add_sample(index, frame.data[index]);
+                }
+            }
+
+            on_sample(progress);
+            self.save_checkpoint_if_due(progress, &mut last_save);
+        }
+    }
+
+    // Brings every pixel of the tile up to `sample_count` samples, working on a copy so the other tiles are not held up
+    fn render_tile_samples(&self, rows: Range<usize>, columns: Range<usize>, sample_count: u32,
+        bvh: &BVH, environment_map: Option<&Vector2D<Color>>, progress: &Mutex<Checkpoint>) {
+        let indices: Vec<usize> = rows.flat_map(|x: usize| columns.clone().map(move |y: usize| x * self.width + y)).collect();
+        let mut pixels: Vec<(Color, u32)> = {
+            let progress: MutexGuard<Checkpoint> = progress.lock().unwrap();
+            indices.iter().map(|&index: &usize| (progress.accumulation.data[index], progress.sample_counts[index])).collect()
+        };
+
+        for (&index, (mean, count)) in indices.iter().zip(pixels.iter_mut()) {
+            while *count < sample_count {
+                let color: Color = self.render_pixel_sample(bvh, environment_map, index / self.width, index % self.width, *count as u64);
+                *mean = Checkpoint::accumulate(*mean, *count, color);
+                *count += 1;
             }
-            frame_out = pixel_accumulation;
         }
 
-        frame_out
+        let mut progress: MutexGuard<Checkpoint> = progress.lock().unwrap();
+        for (&index, &(mean, count)) in indices.iter().zip(&pixels) {
+            progress.accumulation.data[index] = mean;
+            progress.sample_counts[index] = count;
+        }
     }
 
     pub fn render_tile(self: &Camera, start_x: usize, end_x: usize, 
@@ -146,19 +668,13 @@ impl Camera {
         let mut pixel_accumulation: Vector2D<Color> = Vector2D::new(
             self.tile_size as usize, self.tile_size as usize, Color::black()
         );
-        let environment_map: &Vector2D<Color> = &self.scene.environment_map.clone().unwrap();
+        let environment_map: Option<&Vector2D<Color>> = self.scene.environment_map.as_ref();
 
         for s in 0..sample_count {
             old_frame = pixel_accumulation;
             for x in start_y..end_y {
                 for y in start_x..end_x {
-                    let mut pixel_color: Color = Color::black();
-                    for _s in 0..self.rays_per_pixel {
-                        pixel_color += Ray::cast_ray_from_camera(
-                            &self, &bvh, environment_map, x, y
-                        );
-                    }
-                    pixel_color /= self.rays_per_pixel;     
+                    let pixel_color: Color = self.render_pixel_sample(bvh, environment_map, x, y, s as u64);
                     frame.set(x - start_y, y - start_x, pixel_color);
                 }
             }
@@ -170,7 +686,15 @@ impl Camera {
         pixel_accumulation
     }
 
+    // One unseeded sample of the whole frame
     pub fn render_whole_sample(self: &Camera, bvh: &BVH) 
+        -> Vector2D<Color> {
+        self.render_sample(bvh, rand::random::<u64>())
+    }
+
+    // Sample number `sample` of the whole frame, the same every time for the same seed. Pixels outside the
+    // crop are left black.
+    pub fn render_sample(self: &Camera, bvh: &BVH, sample: u64) 
         -> Vector2D<Color> {
         let frame: Vector2D<Color> = Vector2D::new(
             self.width, 
@@ -178,20 +702,17 @@ impl Camera {
             Color::black()
         );
 
-        let vert: Vec<usize> = (0..frame.height).collect();
-        let horz: Vec<usize> = (0..frame.width).collect();
+        let region: CropRegion = self.region();
+        let vert: Vec<usize> = region.rows().collect();
+        let horz: Vec<usize> = region.columns().collect();
         let vert_slice: &[usize] = &vert;
         let horz_slice: &[usize] = &horz;
-        let environment_map: &Vector2D<Color> = &self.scene.environment_map.clone().unwrap();
+        let environment_map: Option<&Vector2D<Color>> = self.scene.environment_map.as_ref();
 
         let frame: Mutex<Vector2D<Color>> = Mutex::new(frame);
         vert_slice.par_iter().for_each(|&x| {
             horz_slice.par_iter().for_each(|&y| {
-                let mut pixel_color: Color = Color::black();
-                for _s in 0..self.rays_per_pixel {
-                    pixel_color += Ray::cast_ray_from_camera(&self, &bvh, environment_map, x, y);
-                }
-                pixel_color /= self.rays_per_pixel;             
+                let pixel_color: Color = self.render_pixel_sample(bvh, environment_map, x, y, sample);
                 let mut frame: MutexGuard<Vector2D<Color>> = frame.lock().unwrap();             
                 frame.set(x, y, pixel_color);
             });
@@ -200,10 +721,132 @@ impl Camera {
         frame.into_inner().unwrap()
     }
 
-    fn get_tiles(width: usize, height: usize, tile_size: usize) -> Vec<(usize, usize)> {
+    // Every ray of a pixel sample draws from that sample's own random stream, see sampling::seed_pixel
+    fn render_pixel_sample(&self, bvh: &BVH, environment_map: Option<&Vector2D<Color>>, x: usize, y: usize, sample: u64) -> Color {
+        sampling::seed_pixel(self.seed, sample, x * self.width + y);
+        let mut pixel_color: Color = Color::black();
+        for _s in 0..self.rays_per_pixel {
+            pixel_color += Ray::cast_ray_from_camera(self, bvh, environment_map, x, y);
+        }
+        pixel_color /= self.rays_per_pixel;
+        pixel_color * self.exposure_scale()
+    }
+
+    fn save_checkpoint_if_due(&self, progress: &Checkpoint, last_save: &mut Instant) {
+        if let Some(settings) = &self.checkpoint {
+            if last_save.elapsed() >= settings.interval {
+                self.save_checkpoint(progress);
+                *last_save = Instant::now();
+            }
+        }
+    }
+
+    // A checkpoint that cannot be written should not end a render that may have hours left
+    fn save_checkpoint(&self, progress: &Checkpoint) {
+        if let Some(settings) = &self.checkpoint {
+            match progress.save(&settings.path) {
+                Ok(()) => println!("Saved checkpoint at {} samples: {}", progress.min_samples(), settings.path),
+                Err(err) => eprintln!("{}", err)
+            }
+        }
+    }
+
+    // First hit data of `sample_count` primary rays per pixel, jittered like the beauty pass
+    pub fn render_aovs(&self, bvh: &BVH, sample_count: u32) -> AovBuffers {
+        if self.aovs.is_empty() {
+            return AovBuffers { buffers: vec![] }
+        }
+        let pixels: Vec<AovAccumulator> = (0..self.width * self.height).into_par_iter().map(|index: usize| {
+            let (x, y) = (index / self.width, index % self.width);
+            let mut accumulator: AovAccumulator = AovAccumulator::new();
+            for _s in 0..sample_count.max(1) {
+                let ray: Ray = Ray::from_camera(self, x, y);
+                if ray.is_empty() {
+                    break
+                }
+                let hit = Tri::ray_collision(ray, bvh);
+                if !hit.is_empty {
+                    accumulator.add_sample(&hit, &self.scene, self.depth(&ray, hit.point));
+                }
+            }
+            accumulator
+        }).collect();
+
+        let buffers: Vec<(Aov, Vector2D<Color>)> = self.aovs.iter().map(|&aov: &Aov| {
+            let mut buffer: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
+            for (pixel, accumulator) in buffer.data.iter_mut().zip(&pixels) {
+                *pixel = accumulator.resolve(aov);
+            }
+            (aov, buffer)
+        }).collect();
+
+        AovBuffers { buffers }
+    }
+
+    // Depth of `point` as seen by the primary ray `ray`: along the view axis of the camera where it is at the
+    // ray's time, and straight along the ray for panoramic projections, which see behind the camera
+    fn depth(&self, ray: &Ray, point: Vector3) -> f64 {
+        match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => (point - ray.origin) * self.pose_at(ray.time).1.forward,
+            Projection::Equirectangular | Projection::Fisheye { .. } => point.distance(ray.origin)
+        }
+    }
+
+    // Object and material coverage mattes from a stratified grid of `samples_per_axis` squared rays per pixel
+    pub fn render_id_mattes(&self, bvh: &BVH, samples_per_axis: usize) -> (IdMatte, IdMatte) {
+        let samples_per_axis: usize = samples_per_axis.max(1);
+        let sample_weight: f32 = 1.0 / (samples_per_axis * samples_per_axis) as f32;
+
+        let pixels: Vec<(CoverageRanks, CoverageRanks)> = (0..self.width * self.height).into_par_iter().map(|index: usize| {
+            let (x, y) = (index / self.width, index % self.width);
+            let mut objects: CoverageRanks = vec![];
+            let mut materials: CoverageRanks = vec![];
+            for i in 0..samples_per_axis * samples_per_axis {
+                let offset: Vector2 = Vector2::new(
+                    ((i / samples_per_axis) as f64 + 0.5) / samples_per_axis as f64 - 0.5,
+                    ((i % samples_per_axis) as f64 + 0.5) / samples_per_axis as f64 - 0.5
+                );
+                let ray: Ray = Ray::from_camera_offset(self, x, y, offset);
+                if ray.is_empty() {
+                    continue
+                }
+                let hit: HitPoint = Tri::ray_collision(ray, bvh);
+                if !hit.is_empty {
+                    Self::add_coverage(&mut objects, hit.object.object_id, sample_weight);
+                    Self::add_coverage(&mut materials, hit.object.material_id, sample_weight);
+                }
+            }
+            for ranks in [&mut objects, &mut materials] {
+                ranks.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            }
+            (objects, materials)
+        }).collect();
+
+        let object_names: Vec<String> = self.scene.meshes.iter().enumerate()
+            .map(|(i, mesh): (usize, &MeshObject)| if mesh.name.is_empty() { format!("object_{}", i) } else { mesh.name.clone() })
+            .collect();
+        let material_names: Vec<String> = (0..MeshObject::unique_materials(&self.scene.meshes).len())
+            .map(|i: usize| format!("material_{}", i))
+            .collect();
+        let (object_pixels, material_pixels) = pixels.into_iter().unzip();
+
+        (
+            IdMatte::new(MatteKind::Object, self.width, self.height, object_names, object_pixels),
+            IdMatte::new(MatteKind::Material, self.width, self.height, material_names, material_pixels)
+        )
+    }
+
+    fn add_coverage(ranks: &mut CoverageRanks, id: usize, weight: f32) {
+        match ranks.iter_mut().find(|(rank_id, _)| *rank_id == id) {
+            Some((_, coverage)) => *coverage += weight,
+            None => ranks.push((id, weight))
+        }
+    }
+
+    fn get_tiles(region: &CropRegion, tile_size: usize) -> Vec<(usize, usize)> {
         let mut tiles: Vec<(usize, usize)> = vec![];
-        for x in (0..width).step_by(tile_size as usize) {
-            for y in (0..height).step_by(tile_size as usize) {
+        for x in region.columns().step_by(tile_size as usize) {
+            for y in region.rows().step_by(tile_size as usize) {
                 tiles.push((x, y))
             }
         }
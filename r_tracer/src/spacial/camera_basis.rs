@@ -0,0 +1,56 @@
+use crate::datatypes::vector3::Vector3;
+use crate::error::RTracerError;
+
+
+// Orthonormal frame of a camera in renderer space. Primary rays leave along `forward`, image columns run
+// along `right` and image rows run against `up`.
+#[derive(Copy, Clone, PartialEq)]
+pub struct CameraBasis {
+    pub forward: Vector3,
+    pub right: Vector3,
+    pub up: Vector3
+}
+
+impl CameraBasis {
+    // The frame Camera::rotation gives: +X forward, +Y right and +Z up, rotated by the Euler angles
+    pub fn from_rotation(rotation: Vector3) -> CameraBasis {
+        CameraBasis {
+            forward: Vector3::new(1.0, 0.0, 0.0).rot(rotation),
+            right: Vector3::new(0.0, 1.0, 0.0).rot(rotation),
+            up: Vector3::new(0.0, 0.0, 1.0).rot(rotation)
+        }
+    }
+
+    // Looks from `eye` towards `target`. `up` only has to point roughly upwards, it is straightened to be
+    // perpendicular to the view direction.
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3) -> Result<CameraBasis, RTracerError> {
+        let forward: Vector3 = (target - eye).normalize();
+        if forward == Vector3::zero() {
+            return Err(RTracerError::invalid_parameter("look_at", "the target is at the camera position"))
+        }
+        // Renderer space is left handed, up x forward points right
+        let right: Vector3 = up.cross(&forward).normalize();
+        if right == Vector3::zero() {
+            return Err(RTracerError::invalid_parameter("look_at", "the up vector is parallel to the view direction"))
+        }
+        Ok(CameraBasis { forward, right, up: forward.cross(&right) })
+    }
+
+    // Renderer space direction of `local`, given as components along forward, right and up
+    pub fn to_world(&self, local: Vector3) -> Vector3 {
+        local.x * self.forward + local.y * self.right + local.z * self.up
+    }
+
+    // Inverse of to_world
+    pub fn to_local(&self, world: Vector3) -> Vector3 {
+        Vector3::new(world * self.forward, world * self.right, world * self.up)
+    }
+
+    // Frame a fraction `t` of the way from this one to `other`. Stays at this one if the two look in
+    // opposite directions, where there is no single way to turn between them.
+    pub fn lerp(&self, other: &CameraBasis, t: f64) -> CameraBasis {
+        let forward: Vector3 = Vector3::lerp(self.forward, other.forward, t);
+        let up: Vector3 = Vector3::lerp(self.up, other.up, t);
+        CameraBasis::look_at(Vector3::zero(), forward, up).unwrap_or(*self)
+    }
+}
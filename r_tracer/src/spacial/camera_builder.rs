@@ -0,0 +1,269 @@
+use std::time::Duration;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::material::{check_range, check_positive};
+use crate::spacial::camera::{Camera, CameraMotion, FieldOfView, Projection, FisheyeMapping};
+use crate::spacial::camera_basis::CameraBasis;
+use crate::spacial::physical_camera::PhysicalCamera;
+use crate::spacial::aperture::Aperture;
+use crate::spacial::stereo::Stereo;
+use crate::spacial::crop_region::CropRegion;
+use crate::spacial::scene::Scene;
+use crate::spacial::aov::Aov;
+use crate::utilities::mesh_cache::MeshCache;
+use crate::error::RTracerError;
+
+
+// Named alternative to Camera::new. Starts from a camera at the origin looking down +X with the same
+// defaults as a scene file camera, and checks the values when built.
+#[derive(Clone)]
+pub struct CameraBuilder {
+    camera: Camera,
+    // Target and up vector, turned into a basis once the position is final
+    look_at: Option<(Vector3, Vector3)>,
+    // Eye, target and up vector of the end of a camera move
+    motion_look_at: Option<(Vector3, Vector3, Vector3)>
+}
+
+impl CameraBuilder {
+    pub fn new(scene: Scene, width: usize, height: usize) -> CameraBuilder {
+        CameraBuilder {
+            camera: Camera::new(
+                Vector3::zero(), Vector3::zero(), scene,
+                1.0, width, height, 8, 1, 0.0, 0.0, 1.0, 1.0, 0
+            ),
+            look_at: None,
+            motion_look_at: None
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector3) -> CameraBuilder {
+        self.camera.position = position;
+        self
+    }
+
+    // Euler angles in degrees, applied about X, then Y, then Z
+    pub fn with_rotation(mut self, rotation: Vector3) -> CameraBuilder {
+        self.camera.rotation = rotation;
+        self
+    }
+
+    // Aims the camera from `eye` at `target` instead of using the rotation
+    pub fn with_look_at(mut self, eye: Vector3, target: Vector3, up: Vector3) -> CameraBuilder {
+        self.camera.position = eye;
+        self.look_at = Some((target, up));
+        self
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> CameraBuilder {
+        self.camera.exposure = exposure;
+        self
+    }
+
+    pub fn with_max_bounces(mut self, max_bounces: u32) -> CameraBuilder {
+        self.camera.max_bounces = max_bounces;
+        self
+    }
+
+    pub fn with_rays_per_pixel(mut self, rays_per_pixel: u32) -> CameraBuilder {
+        self.camera.rays_per_pixel = rays_per_pixel;
+        self
+    }
+
+    pub fn with_blur_strength(mut self, blur_strength: f64) -> CameraBuilder {
+        self.camera.blur_strength = blur_strength;
+        self
+    }
+
+    // Radius of the lens and the distance that stays in focus
+    pub fn with_depth_of_field(mut self, dof_strength: f64, focal_distance: f64) -> CameraBuilder {
+        self.camera.dof_strength = dof_strength;
+        self.camera.focal_distance = focal_distance;
+        self
+    }
+
+    pub fn with_fov(mut self, fov: f64) -> CameraBuilder {
+        self.camera.fov = fov;
+        self
+    }
+
+    pub fn with_horizontal_fov(mut self, degrees: f64) -> CameraBuilder {
+        self.camera = self.camera.with_horizontal_fov(degrees);
+        self
+    }
+
+    pub fn with_vertical_fov(mut self, degrees: f64) -> CameraBuilder {
+        self.camera = self.camera.with_vertical_fov(degrees);
+        self
+    }
+
+    // Parallel projection, `scale` world units across the wider side of the image
+    pub fn with_orthographic(mut self, scale: f64) -> CameraBuilder {
+        self.camera = self.camera.with_orthographic(scale);
+        self
+    }
+
+    pub fn with_equirectangular(mut self) -> CameraBuilder {
+        self.camera = self.camera.with_equirectangular();
+        self
+    }
+
+    // `fov` degrees across the image circle, up to 360
+    pub fn with_fisheye(mut self, mapping: FisheyeMapping, fov: f64) -> CameraBuilder {
+        self.camera = self.camera.with_fisheye(mapping, fov);
+        self
+    }
+
+    // Derives the field of view, lens radius and exposure from real camera settings
+    pub fn with_physical(mut self, physical: PhysicalCamera) -> CameraBuilder {
+        self.camera = self.camera.with_physical(physical);
+        self
+    }
+
+    // Shape of the lens opening, a disk unless set
+    pub fn with_aperture(mut self, aperture: Aperture) -> CameraBuilder {
+        self.camera = self.camera.with_aperture(aperture);
+        self
+    }
+
+    // Times between 0 and 1 the shutter is open for, see Camera::shutter_open
+    pub fn with_shutter(mut self, open: f64, close: f64) -> CameraBuilder {
+        self.camera = self.camera.with_shutter(open, close);
+        self
+    }
+
+    // Moves the camera to `position` and turns it to `rotation` over the frame
+    pub fn with_motion(mut self, position: Vector3, rotation: Vector3) -> CameraBuilder {
+        self.camera = self.camera.with_motion(CameraMotion::new(position, rotation));
+        self.motion_look_at = None;
+        self
+    }
+
+    // Moves the camera to `eye` over the frame, ending up looking at `target`
+    pub fn with_motion_look_at(mut self, eye: Vector3, target: Vector3, up: Vector3) -> CameraBuilder {
+        self.motion_look_at = Some((eye, target, up));
+        self
+    }
+
+    // Eyes `interocular_distance` apart whose views line up `convergence_distance` away, for render_stereo
+    pub fn with_stereo(mut self, interocular_distance: f64, convergence_distance: f64) -> CameraBuilder {
+        self.camera = self.camera.with_stereo(Stereo::new(interocular_distance, convergence_distance));
+        self
+    }
+
+    // Renders only this rectangle of the frame
+    pub fn with_crop(mut self, crop: CropRegion) -> CameraBuilder {
+        self.camera = self.camera.with_crop(crop);
+        self
+    }
+
+    // 0 renders progressively, whole frames at a time
+    pub fn with_tile_size(mut self, tile_size: usize) -> CameraBuilder {
+        self.camera.tile_size = tile_size;
+        self
+    }
+
+    pub fn with_aovs(mut self, aovs: Vec<Aov>) -> CameraBuilder {
+        self.camera = self.camera.with_aovs(aovs);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: MeshCache) -> CameraBuilder {
+        self.camera = self.camera.with_cache(cache);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> CameraBuilder {
+        self.camera = self.camera.with_seed(seed);
+        self
+    }
+
+    pub fn with_checkpoint(mut self, path: &str, interval: Duration) -> CameraBuilder {
+        self.camera = self.camera.with_checkpoint(path, interval);
+        self
+    }
+
+    pub fn build(self) -> Result<Camera, RTracerError> {
+        let mut camera: Camera = self.camera;
+        if camera.width == 0 || camera.height == 0 {
+            return Err(RTracerError::invalid_parameter("resolution",
+                format!("must be at least 1x1, got {}x{}", camera.width, camera.height)))
+        }
+        if camera.rays_per_pixel == 0 {
+            return Err(RTracerError::invalid_parameter("rays_per_pixel", "must be at least 1"))
+        }
+        check_finite("position", camera.position)?;
+        check_finite("rotation", camera.rotation)?;
+
+        check_range("exposure", camera.exposure, 0.0, f64::INFINITY)?;
+        check_range("blur_strength", camera.blur_strength, 0.0, f64::INFINITY)?;
+        check_range("dof_strength", camera.dof_strength, 0.0, f64::INFINITY)?;
+        check_positive("focal_distance", camera.focal_distance)?;
+        check_positive("fov", camera.fov)?;
+        check_projection(&camera)?;
+        check_range("shutter_open", camera.shutter_open, 0.0, 1.0)?;
+        check_range("shutter_close", camera.shutter_close, camera.shutter_open, 1.0)?;
+        if let Some(checkpoint) = &camera.checkpoint {
+            if checkpoint.interval.is_zero() {
+                return Err(RTracerError::invalid_parameter("checkpoint interval", "must be longer than 0"))
+            }
+        }
+
+        if let Some(physical) = &camera.physical {
+            physical.validate()?;
+        }
+        camera.aperture.validate()?;
+        if let Some(stereo) = &camera.stereo {
+            stereo.validate()?;
+        }
+        if let Some(crop) = &camera.crop {
+            crop.validate(camera.width, camera.height)?;
+        }
+        if let Some((target, up)) = self.look_at {
+            check_finite("look_at target", target)?;
+            check_finite("look_at up", up)?;
+            camera.basis = Some(CameraBasis::look_at(camera.position, target, up)?);
+        }
+        if let Some((eye, target, up)) = self.motion_look_at {
+            check_finite("motion eye", eye)?;
+            check_finite("motion target", target)?;
+            check_finite("motion up", up)?;
+            camera.motion = Some(CameraMotion::look_at(eye, target, up)?);
+        }
+        if let Some(motion) = camera.motion {
+            check_finite("motion position", motion.position)?;
+            check_finite("motion rotation", motion.basis.forward)?;
+        }
+
+        Ok(camera)
+    }
+}
+
+fn check_finite(name: &str, vector: Vector3) -> Result<(), RTracerError> {
+    if !(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()) {
+        return Err(RTracerError::invalid_parameter(name, "must be finite"))
+    }
+    Ok(())
+}
+
+fn check_projection(camera: &Camera) -> Result<(), RTracerError> {
+    match camera.field_of_view {
+        Some(FieldOfView::Horizontal(degrees)) => check_angle("horizontal_fov", degrees)?,
+        Some(FieldOfView::Vertical(degrees)) => check_angle("vertical_fov", degrees)?,
+        None => ()
+    }
+    match camera.projection {
+        Projection::Orthographic { scale } => check_positive("orthographic_scale", scale),
+        Projection::Fisheye { fov, .. } if !(fov > 0.0 && fov <= 360.0) => {
+            Err(RTracerError::invalid_parameter("fisheye_fov", format!("must be between 0 and 360 degrees, got {}", fov)))
+        },
+        _ => Ok(())
+    }
+}
+
+// A pinhole image plane only covers angles below 180 degrees
+fn check_angle(name: &str, degrees: f64) -> Result<(), RTracerError> {
+    if !(degrees > 0.0 && degrees < 180.0) {
+        return Err(RTracerError::invalid_parameter(name, format!("must be between 0 and 180 degrees, got {}", degrees)))
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::ops::Range;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::error::RTracerError;
+
+
+// Rectangle of pixels a render is limited to, rows `top` to `top + height` and columns `left` to `left + width`
+// of the full frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CropRegion {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl CropRegion {
+    pub fn new(left: usize, top: usize, width: usize, height: usize) -> CropRegion {
+        CropRegion { left, top, width, height }
+    }
+
+    pub fn full(width: usize, height: usize) -> CropRegion {
+        CropRegion::new(0, 0, width, height)
+    }
+
+    pub fn rows(&self) -> Range<usize> {
+        self.top..self.top + self.height
+    }
+
+    pub fn columns(&self) -> Range<usize> {
+        self.left..self.left + self.width
+    }
+
+    // Indices into the data of a frame `frame_width` pixels wide, row by row
+    pub fn indices(&self, frame_width: usize) -> impl Iterator<Item = usize> {
+        let columns: Range<usize> = self.columns();
+        self.rows().flat_map(move |x: usize| columns.clone().map(move |y: usize| x * frame_width + y))
+    }
+
+    // The region's pixels of a full frame, as an image of their own
+    pub fn crop(&self, frame: &Vector2D<Color>) -> Vector2D<Color> {
+        let mut image: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
+        for (pixel, index) in image.data.iter_mut().zip(self.indices(frame.width)) {
+            *pixel = frame.data[index];
+        }
+        image
+    }
+
+    // Copies the region's pixels of `frame` onto the same place of `image`, leaving the rest of it alone
+    pub fn composite(&self, frame: &Vector2D<Color>, image: &mut Vector2D<Color>) {
+        for index in self.indices(frame.width) {
+            image.data[index] = frame.data[index];
+        }
+    }
+
+    pub fn validate(&self, frame_width: usize, frame_height: usize) -> Result<(), RTracerError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(RTracerError::invalid_parameter("crop region", format!("must be at least 1x1, got {}x{}", self.width, self.height)))
+        }
+        if self.left.saturating_add(self.width) > frame_width || self.top.saturating_add(self.height) > frame_height {
+            return Err(RTracerError::invalid_parameter("crop region", format!(
+                "{}x{} at column {}, row {} reaches outside the {}x{} frame",
+                self.width, self.height, self.left, self.top, frame_width, frame_height
+            )))
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use exr::prelude::{AnyChannel, AnyChannels, AttributeValue, Encoding, FlatSamples, Image, Layer, LayerAttributes,
+    SmallVec, Text, WritableImage};
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::error::RTracerError;
+
+
+// Which identity of the hit triangle a matte separates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MatteKind {
+    Object,
+    Material
+}
+
+impl MatteKind {
+    // Conventional Cryptomatte layer names
+    pub fn layer_name(self) -> &'static str {
+        match self {
+            MatteKind::Object => "CryptoObject",
+            MatteKind::Material => "CryptoMaterial"
+        }
+    }
+}
+
+// (id, coverage) pairs of one pixel
+pub type CoverageRanks = Vec<(usize, f32)>;
+
+// Per pixel coverage of every id seen in that pixel, sorted by coverage. Coverage is the fraction of
+// the pixel's samples that hit the id, so edges come out anti-aliased and background pixels sum to less than 1.
+#[derive(Clone)]
+pub struct IdMatte {
+    pub kind: MatteKind,
+    pub width: usize,
+    pub height: usize,
+    // Name of each id, ids with the same name share a matte
+    pub names: Vec<String>,
+    pub pixels: Vec<CoverageRanks>
+}
+
+impl IdMatte {
+    pub fn new(kind: MatteKind, width: usize, height: usize, names: Vec<String>, pixels: Vec<CoverageRanks>) -> IdMatte {
+        IdMatte { kind, width, height, names, pixels }
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|id_name: &String| id_name == name)
+    }
+
+    // Coverage of a single id, for grading it separately
+    pub fn coverage(&self, id: usize) -> Vector2D<Color> {
+        let mut matte: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
+        for (pixel, ranks) in matte.data.iter_mut().zip(&self.pixels) {
+            let coverage: f64 = ranks.iter().filter(|(rank_id, _)| self.names[*rank_id] == self.names[id])
+                .fold(0.0, |sum: f64, (_, coverage)| sum + *coverage as f64);
+            *pixel = Color::new(coverage, coverage, coverage);
+        }
+        matte
+    }
+
+    // Id with the highest coverage per pixel, -1 where nothing was hit
+    pub fn id_buffer(&self) -> Vector2D<Color> {
+        let mut buffer: Vector2D<Color> = Vector2D::new(self.width, self.height, Color::black());
+        for (pixel, ranks) in buffer.data.iter_mut().zip(&self.pixels) {
+            let id: f64 = ranks.first().map_or(-1.0, |(id, _)| *id as f64);
+            *pixel = Color::new(id, id, id);
+        }
+        buffer
+    }
+
+    // Writes the Cryptomatte layers (`<layer>00` holds ranks 0 and 1 as id, coverage, id, coverage and so on)
+    // with the name manifest in the header. `levels` is the number of ranks kept per pixel, rounded up to even.
+    pub fn save_exr(&self, filename: &str, levels: usize) -> Result<(), RTracerError> {
+        let layer_name: &str = self.kind.layer_name();
+        let hashes: Vec<f32> = self.names.iter().map(|name: &String| name_to_float(name)).collect();
+        let pixel_count: usize = self.width * self.height;
+
+        let mut channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = SmallVec::new();
+        for layer in 0..levels.max(1).div_ceil(2) {
+            let mut samples: [Vec<f32>; 4] = std::array::from_fn(|_| vec![0.0; pixel_count]);
+            for (index, ranks) in self.pixels.iter().enumerate() {
+                for (rank, &(id, coverage)) in ranks.iter().skip(2 * layer).take(2).enumerate() {
+                    samples[2 * rank][index] = hashes[id];
+                    samples[2 * rank + 1][index] = coverage;
+                }
+            }
+            for (channel, values) in ["R", "G", "B", "A"].iter().zip(samples) {
+                channels.push(AnyChannel::new(format!("{}{:02}.{}", layer_name, layer, channel).as_str(), FlatSamples::F32(values)));
+            }
+        }
+
+        let mut attributes: LayerAttributes = LayerAttributes::default();
+        let prefix: String = format!("cryptomatte/{}", &hash_hex(murmur_hash3_32(layer_name.as_bytes()))[..7]);
+        let metadata: [(&str, String); 4] = [
+            ("name", layer_name.to_string()),
+            ("hash", "MurmurHash3_32".to_string()),
+            ("conversion", "uint32_to_float32".to_string()),
+            ("manifest", self.manifest())
+        ];
+        for (key, value) in metadata {
+            let text: Text = Text::new_or_none(&value)
+                .ok_or_else(|| RTracerError::image_write(filename, format!("cannot store {} in the EXR header", key)))?;
+            attributes.other.insert(Text::new_or_panic(format!("{}/{}", prefix, key)), AttributeValue::Text(text));
+        }
+
+        let layer = Layer::new((self.width, self.height), attributes, Encoding::SMALL_LOSSLESS, AnyChannels::sort(channels));
+        Image::from_layer(layer).write().to_file(filename)
+            .map_err(|err: exr::error::Error| RTracerError::image_write(filename, err))
+    }
+
+    // JSON object mapping every name to the hex of its hash
+    pub fn manifest(&self) -> String {
+        let mut names: Vec<&String> = self.names.iter().collect();
+        names.sort();
+        names.dedup();
+        let entries: Vec<String> = names.iter()
+            .map(|name: &&String| format!("{}:\"{}\"", json_string(name), hash_hex(name_to_float(name).to_bits())))
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+}
+
+// Cryptomatte ids are the MurmurHash3 of the name reinterpreted as a float, with the exponent
+// nudged so the result is never infinite, NaN or denormal
+pub fn name_to_float(name: &str) -> f32 {
+    let mut hash: u32 = murmur_hash3_32(name.as_bytes());
+    let exponent: u32 = (hash >> 23) & 255;
+    if exponent == 0 || exponent == 255 {
+        hash ^= 1 << 23;
+    }
+    f32::from_bits(hash)
+}
+
+fn hash_hex(hash: u32) -> String {
+    format!("{:08x}", hash)
+}
+
+fn murmur_hash3_32(data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+    let mut hash: u32 = 0;
+
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let k: u32 = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    let tail: &[u8] = blocks.remainder();
+    if !tail.is_empty() {
+        let k: u32 = tail.iter().rev().fold(0, |k: u32, &byte: &u8| (k << 8) | byte as u32);
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^ (hash >> 16)
+}
+
+// EXR header strings are single byte characters, so everything outside ASCII is escaped
+fn json_string(value: &str) -> String {
+    let mut escaped: String = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            c => {
+                let mut units: [u16; 2] = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+    escaped.push('"');
+    escaped
+}
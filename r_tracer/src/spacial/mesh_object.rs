@@ -1,9 +1,14 @@
 use crate::spacial::tri::Tri;
+use crate::spacial::bvh::BVH;
+use crate::spacial::motion::Motion;
+use crate::datatypes::material::Material;
+use crate::datatypes::vector3::Vector3;
 
 #[derive(Clone)]
 pub struct MeshObject {
     pub tris: Vec<Tri>,
-    pub smooth_shading: bool
+    pub smooth_shading: bool,
+    pub name: String
 }
 
 impl MeshObject {
@@ -11,6 +16,39 @@ impl MeshObject {
         for i in 0..tris.len() {
             tris[i].smooth_shading = smooth_shading;
         }
-        MeshObject { tris: tris, smooth_shading }
+        MeshObject { tris: tris, smooth_shading, name: String::new() }
+    }
+
+    pub fn with_name(mut self, name: &str) -> MeshObject {
+        self.name = name.to_string();
+        self
+    }
+
+    // Where the mesh ends up at the end of the frame: turned by `rotation` degrees about the center of its
+    // bounding box, then moved by `translation`. In between it turns and moves evenly, see Motion.
+    pub fn with_motion(mut self, translation: Vector3, rotation: Vector3) -> MeshObject {
+        let bounds: Option<(Vector3, Vector3)> = self.tris.iter()
+            .map(|tri: &Tri| (tri.p1.min(tri.p2.min(tri.p3)), tri.p1.max(tri.p2.max(tri.p3))))
+            .reduce(BVH::merge_bounding_boxes);
+        let pivot: Vector3 = match bounds {
+            Some(bounds) => Tri::get_bounding_box_center(bounds),
+            None => return self
+        };
+        let motion: Motion = Motion::new(translation, rotation, pivot);
+        for tri in self.tris.iter_mut() {
+            *tri = tri.with_motion(motion);
+        }
+        self
+    }
+
+    // Distinct materials in the order they first appear, a triangle's material_id indexes into this
+    pub fn unique_materials(mesh_objects: &[MeshObject]) -> Vec<Material> {
+        let mut materials: Vec<Material> = vec![];
+        for tri in mesh_objects.iter().flat_map(|mesh: &MeshObject| &mesh.tris) {
+            if materials.last() != Some(&tri.material) && !materials.contains(&tri.material) {
+                materials.push(tri.material);
+            }
+        }
+        materials
     }
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+use crate::datatypes::vector3::Vector3;
+
+
+// Rigid motion over the frame: at time t a point is turned by t * `rotation` degrees about `pivot`, then moved
+// by t * `translation`
+#[derive(Copy, Clone, PartialEq)]
+pub struct Motion {
+    pub translation: Vector3,
+    pub rotation: Vector3,
+    pub pivot: Vector3
+}
+
+impl Motion {
+    pub fn new(translation: Vector3, rotation: Vector3, pivot: Vector3) -> Motion {
+        Motion { translation, rotation, pivot }
+    }
+
+    pub fn point_at(&self, point: Vector3, time: f64) -> Vector3 {
+        self.pivot + (point - self.pivot).rot(time * self.rotation) + time * self.translation
+    }
+
+    // Directions such as normals and tangents only turn
+    pub fn direction_at(&self, direction: Vector3, time: f64) -> Vector3 {
+        direction.rot(time * self.rotation)
+    }
+
+    // Upper bound on how far a point `radius` away from the pivot travels over the frame. The angle turned
+    // is at most the sum of the Euler angles, so the arc is at most that many radians of the radius.
+    pub fn path_length(&self, radius: f64) -> f64 {
+        let angle: f64 = self.rotation.x.abs().to_radians() + self.rotation.y.abs().to_radians() + self.rotation.z.abs().to_radians();
+        radius * angle + self.translation.magnitude()
+    }
+}
@@ -0,0 +1,82 @@
+use crate::spacial::camera::FieldOfView;
+use crate::datatypes::material::check_positive;
+use crate::error::RTracerError;
+
+
+// Settings of a real camera. When a Camera has one, its field of view, lens radius and image brightness are
+// derived from these instead of `fov`, `dof_strength` and the sensor response being 1. Lengths on the
+// camera side are in millimetres, the scene is taken to be `units_per_meter` world units to the metre.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicalCamera {
+    pub sensor_width: f64,
+    pub sensor_height: f64,
+    pub focal_length: f64,
+    pub f_number: f64,
+    // Seconds
+    pub shutter_time: f64,
+    pub iso: f64,
+    pub units_per_meter: f64
+}
+
+impl PhysicalCamera {
+    // On a full frame (36 x 24 mm) sensor, in a scene modelled in metres
+    pub fn new(focal_length: f64, f_number: f64, shutter_time: f64, iso: f64) -> PhysicalCamera {
+        PhysicalCamera {
+            sensor_width: 36.0,
+            sensor_height: 24.0,
+            focal_length,
+            f_number,
+            shutter_time,
+            iso,
+            units_per_meter: 1.0
+        }
+    }
+
+    pub fn with_sensor(mut self, width: f64, height: f64) -> PhysicalCamera {
+        self.sensor_width = width;
+        self.sensor_height = height;
+        self
+    }
+
+    pub fn with_units_per_meter(mut self, units_per_meter: f64) -> PhysicalCamera {
+        self.units_per_meter = units_per_meter;
+        self
+    }
+
+    // The image is cropped out of the middle of the sensor, so it spans the full sensor width when it is
+    // relatively wider than the sensor and the full sensor height otherwise
+    pub fn field_of_view(&self, width: usize, height: usize) -> FieldOfView {
+        let angle = |sensor_size: f64| -> f64 { 2.0 * (sensor_size / (2.0 * self.focal_length)).atan().to_degrees() };
+        if width as f64 * self.sensor_height >= height as f64 * self.sensor_width {
+            FieldOfView::Horizontal(angle(self.sensor_width))
+        } else {
+            FieldOfView::Vertical(angle(self.sensor_height))
+        }
+    }
+
+    // Radius of the entrance pupil of a thin lens, in world units
+    pub fn aperture_radius(&self) -> f64 {
+        self.focal_length / (2.0 * self.f_number) / 1000.0 * self.units_per_meter
+    }
+
+    // Exposure value at ISO 100, 0 for one second at f/1
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_time).log2() - (self.iso / 100.0).log2()
+    }
+
+    // Scale from scene radiance, taken as luminance in cd/m², to pixel values. Follows the saturation based
+    // sensitivity of ISO 12232, so a pixel reaches 1 at the luminance that saturates a real sensor.
+    pub fn exposure_scale(&self) -> f64 {
+        1.0 / (1.2 * 2f64.powf(self.ev100()))
+    }
+
+    pub fn validate(&self) -> Result<(), RTracerError> {
+        check_positive("sensor_width", self.sensor_width)?;
+        check_positive("sensor_height", self.sensor_height)?;
+        check_positive("focal_length", self.focal_length)?;
+        check_positive("f_number", self.f_number)?;
+        check_positive("shutter_time", self.shutter_time)?;
+        check_positive("iso", self.iso)?;
+        check_positive("units_per_meter", self.units_per_meter)
+    }
+}
@@ -1,7 +1,7 @@
 use crate::datatypes::vector3::Vector3;
 use crate::datatypes::vector2::Vector2;
 use crate::datatypes::vector2d::Vector2D;
-use crate::spacial::camera::Camera;
+use crate::spacial::camera::{Camera, Projection};
 use crate::datatypes::color::Color;
 use crate::datatypes::hit_point::HitPoint;
 use crate::spacial::tri::Tri;
@@ -9,30 +9,39 @@ use crate::spacial::bvh::BVH;
 use crate::datatypes::material::Material;
 use crate::spacial::scene::Scene;
 use std::f64::consts::PI;
-use rand::Rng;
+use crate::utilities::sampling;
 
 
 #[derive(Copy, Clone)]
 pub struct Ray {
     pub origin: Vector3,
-    pub direction: Vector3
+    pub direction: Vector3,
+    // Moment in the frame the ray is traced at, 0 at the start and 1 at the end
+    pub time: f64
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3) -> Ray {
         Ray {
             origin: origin,
-            direction: direction
+            direction: direction,
+            time: 0.0
         }
     }
     
     pub fn empty() -> Ray {
         Ray {
             origin: Vector3::zero(),
-            direction: Vector3::zero()
+            direction: Vector3::zero(),
+            time: 0.0
         }
     }
 
+    pub fn with_time(mut self, time: f64) -> Ray {
+        self.time = time;
+        self
+    }
+
     pub fn reflect(self, normal: Vector3) -> Vector3 {
         (self.direction - 2.0*normal*self.direction*normal).normalize()
     }
@@ -63,24 +72,57 @@ impl Ray {
         true
     }
 
-    pub fn cast_ray_from_camera(camera: &Camera, bvh: &BVH, environment_map: &Vector2D<Color>, x: usize, y: usize) -> Color {
-        let projection_point = camera.blur_strength *
-        Vector3::random_perturb(Vector2::new(camera.width as f64, camera.height as f64)) + 
-        Vector3::new(
-            (camera.width as f64) / camera.fov,
-            y as f64 - (camera.width as f64)/2.0, 
-            (camera.height as f64)/2.0 - x as f64
-        ).normalize().rot(camera.rotation);
+    pub fn cast_ray_from_camera(camera: &Camera, bvh: &BVH, environment_map: Option<&Vector2D<Color>>, x: usize, y: usize) -> Color {
+        let ray: Ray = Ray::from_camera(camera, x, y);
+        if ray.is_empty() {
+            return Color::black()
+        }
+        ray.cast_ray(bvh, camera.max_bounces, camera.exposure, &camera.scene, environment_map)
+    }
 
-        let focal_point: Vector3 = camera.position + camera.focal_distance * projection_point.normalize();
-        let ray_origin: Vector3 = camera.position + camera.dof_strength * Vector3::random_perturb(Vector2::new(1.0, 1.0));
+    // Primary rays of pixels the camera does not see through, such as outside a fisheye's image circle
+    pub fn is_empty(&self) -> bool {
+        self.direction == Vector3::zero()
+    }
+
+    // Primary ray through pixel row x, column y, jittered by the camera's blur and depth of field. Empty for
+    // pixels outside the view of the camera.
+    pub fn from_camera(camera: &Camera, x: usize, y: usize) -> Ray {
+        Self::from_camera_offset(camera, x, y, Vector2::zero())
+    }
+
+    // Same as from_camera, shifted within the pixel by `pixel_offset` (rows, columns)
+    pub fn from_camera_offset(camera: &Camera, x: usize, y: usize, pixel_offset: Vector2) -> Ray {
+        let mut right: f64 = y as f64 + pixel_offset.y - (camera.width as f64)/2.0;
+        let mut up: f64 = (camera.height as f64)/2.0 - x as f64 - pixel_offset.x;
+        let view_direction: Vector3 = camera.view_direction(right, up);
+        if view_direction == Vector3::zero() {
+            return Ray::empty()
+        }
+        // Parallel rays all point forward, so orthographic blur moves where on the image plane the ray starts
+        // rather than which way it goes
+        let projection_point: Vector3 = match camera.projection {
+            Projection::Orthographic { .. } => {
+                let jitter: Vector3 = camera.blur_strength * Vector3::random_in_unit_disk();
+                right += jitter.x;
+                up += jitter.y;
+                view_direction
+            },
+            _ => camera.blur_strength *
+                Vector3::random_perturb(Vector2::new(camera.width as f64, camera.height as f64)) +
+                view_direction
+        };
+        let eye: Vector3 = camera.view_origin(right, up);
+
+        let focal_point: Vector3 = eye + camera.focal_distance * projection_point.normalize();
+        // The lens lies across the view, in the plane of the camera's right and up axes
+        let ray_origin: Vector3 = eye + camera.lens_radius() * camera.to_world(camera.aperture.sample());
         let ray_direction: Vector3 = (focal_point - ray_origin).normalize();
 
-        Ray::new(ray_origin, ray_direction)
-            .cast_ray(bvh, camera.max_bounces, camera.exposure, &camera.scene, environment_map)
+        camera.move_to_time(Ray::new(ray_origin, ray_direction).with_time(camera.sample_time()))
     }
 
-    pub fn cast_ray(mut self, bvh: &BVH, max_bounces: u32, exposure: f64, scene: &Scene, environment_map: &Vector2D<Color>) -> Color {
+    pub fn cast_ray(mut self, bvh: &BVH, max_bounces: u32, exposure: f64, scene: &Scene, environment_map: Option<&Vector2D<Color>>) -> Color {
 
         let mut hit_point: HitPoint;
         let mut incoming_light: Color = Color::black();
@@ -99,7 +141,7 @@ impl Ray {
                 ) = Self::get_maps(&hit_point, scene);
 
                 let material: Material = hit_point.object.material;
-                let random_val: f64 = rand::thread_rng().gen_range(0.0..1.0);
+                let random_val: f64 = sampling::random();
 
                 self.origin = hit_point.point;
                 self.direction = self.ray_redirect(
@@ -134,10 +176,7 @@ impl Ray {
             smoothness_map_value: f64, specular_map_value: f64) -> Vector3 {        
         let mat: Material = hit.object.material;
         let is_specular_bounce = (specular_map_value >= random_val) as u8 as f64;
-        let mut normal: Vector3 = hit.normal;
-        if normal_map_vector != Vector3::zero() {
-            normal = (normal + normal_map_vector*hit.object.material.normal_strength).normalize();
-        }
+        let normal: Vector3 = Self::shading_normal(&hit, normal_map_vector);
 
         let diffuse_direction: Vector3 = Vector3::random_hemisphere_normal(normal);
         let specular_direction: Vector3 = self.reflect(normal);
@@ -148,7 +187,7 @@ impl Ray {
         if mat.dielectric > 0.0 {
             let mut ior: f64 = mat.index_of_refraction;
             if hit.is_front_face { ior = 1.0 / ior }
-            let random_val_2: f64 = rand::thread_rng().gen_range(0.0..1.0);
+            let random_val_2: f64 = sampling::random();
     
             let cos_theta: f64 = f64::min(-1.0 * self.direction * normal, 1.0);
             let sin_theta: f64 = (1.0 - cos_theta * cos_theta).sqrt();
@@ -156,7 +195,7 @@ impl Ray {
             if ior * sin_theta > 1.0 || Self::get_reflectance(cos_theta, ior) > random_val_2 {
                 return glossy_direction
             } else {
-                let random_val_3 = rand::thread_rng().gen_range(0.0..1.0);
+                let random_val_3 = sampling::random();
                 let is_dielectric_bounce = (mat.dielectric >= random_val_3) as u8 as f64;
                 let refracted_direction = Vector3::lerp(
                     -1.0*diffuse_direction, self.refract_precomputed_cos_theta(normal, ior, cos_theta), smoothness_map_value
@@ -168,13 +207,22 @@ impl Ray {
         }
     }
 
+    // Geometric normal perturbed by the normal map
+    pub(crate) fn shading_normal(hit: &HitPoint, normal_map_vector: Vector3) -> Vector3 {
+        if normal_map_vector != Vector3::zero() {
+            (hit.normal + normal_map_vector*hit.object.material.normal_strength).normalize()
+        } else {
+            hit.normal
+        }
+    }
+
     fn get_reflectance(cosine: f64, ior: f64) -> f64 {
         let mut r0: f64 = (1.0 - ior) / (1.0 + ior);
         r0 *= r0;
         r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
 
-    fn get_maps(hit: &HitPoint, scene: &Scene) -> (Color, Color, Color, Color, Vector3, f64, f64) {
+    pub(crate) fn get_maps(hit: &HitPoint, scene: &Scene) -> (Color, Color, Color, Color, Vector3, f64, f64) {
         let mut diffuse_col: Color = hit.object.material.diffuse_color;
         let mut emission_col: Color = hit.object.material.emission_color;
         let mut specular_col: Color = hit.object.material.specular_color;
@@ -187,7 +235,11 @@ impl Ray {
             + hit.barycentric_coords.y*hit.object.p2_texture
             + hit.barycentric_coords.z*hit.object.p3_texture;
 
-        if hit.object.material.diffuse_color_map_index != None {
+        if hit.object.material.use_vertex_colors {
+            diffuse_col = hit.object.p1_color*hit.barycentric_coords.x
+                + hit.object.p2_color*hit.barycentric_coords.y
+                + hit.object.p3_color*hit.barycentric_coords.z;
+        } else if hit.object.material.diffuse_color_map_index != None {
             diffuse_col = Self::get_map_color(scene, uv, hit.object.material.diffuse_color_map_index.unwrap());
         }
         if hit.object.material.emission_color_map_index != None {
@@ -203,6 +255,16 @@ impl Ray {
             normal_map_vec = (Self::get_map_color(
                 scene, uv, hit.object.material.normal_map_index.unwrap()
             ).to_vector3() * 2.0 - Vector3::one()).normalize();
+
+            // Meshes that carry tangents get their normal map sampled in tangent space
+            if hit.object.has_tangents() {
+                let tangent: Vector3 = (hit.barycentric_coords.x*hit.object.p1_tangent
+                    + hit.barycentric_coords.y*hit.object.p2_tangent
+                    + hit.barycentric_coords.z*hit.object.p3_tangent).normalize();
+                let bitangent: Vector3 = hit.object.bitangent_sign * hit.normal.cross(&tangent);
+                normal_map_vec = (normal_map_vec.x*tangent + normal_map_vec.y*bitangent
+                    + normal_map_vec.z*hit.normal).normalize();
+            }
         }
         if hit.object.material.smoothness_map_index != None {
             smoothness_map_val = Self::get_map_color(
@@ -231,15 +293,19 @@ impl Ray {
         *map.get(wrapped_y as usize, wrapped_x as usize)
     }
     
-    fn get_environment_color(scene: &Scene, map: &Vector2D<Color>, angle: Vector3) -> Color {
-        if scene.environment_map == None { return scene.env_color }
+    // The flat environment color for scenes without a map
+    fn get_environment_color(scene: &Scene, map: Option<&Vector2D<Color>>, angle: Vector3) -> Color {
+        let map: &Vector2D<Color> = match map {
+            Some(map) => map,
+            None => return scene.env_color
+        };
 
-        let azimuth: f64 = f64::atan2(angle.x, angle.y);
-        let elevation: f64 = f64::asin(angle.z);
+        let azimuth: f64 = f64::atan2(angle.x, angle.y) + scene.environment_rotation.to_radians();
+        let elevation: f64 = f64::asin(angle.z.clamp(-1.0, 1.0));
 
         let width: f64 = map.width as f64;
         let height: f64 = map.height as f64;
-        let mut pixel_x: f64 = (azimuth / (2.0 * PI)) * width as f64;
+        let mut pixel_x: f64 = (azimuth / (2.0 * PI)).rem_euclid(1.0) * width as f64;
         let mut pixel_y: f64 = height - (elevation / PI + 0.5) * height as f64;
 
         if pixel_x < 0.0 { pixel_x += width; }
@@ -247,7 +313,8 @@ impl Ray {
         if pixel_y < 0.0 { pixel_y += height; }
         else if pixel_y >= height { pixel_y -= height; }
 
-        *map.get(pixel_y as usize, pixel_x as usize)
+        *map.get(usize::min(pixel_y as usize, map.height - 1), usize::min(pixel_x as usize, map.width - 1))
+            * scene.environment_intensity
     }
     
 }
\ No newline at end of file
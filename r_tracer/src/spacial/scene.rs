@@ -1,6 +1,10 @@
 use crate::spacial::mesh_object::MeshObject;
 use crate::datatypes::color::Color;
 use crate::datatypes::vector2d::Vector2D;
+use crate::utilities::scene_file::{load_scene, SceneFileError};
+use crate::utilities::obj_export::export_obj;
+use crate::utilities::import_options::ImportOptions;
+use crate::error::RTracerError;
 
 
 #[derive(Clone)]
@@ -8,7 +12,13 @@ pub struct Scene {
     pub meshes: Vec<MeshObject>,
     pub texture_maps: Vec<Vector2D<Color>>,
     pub env_color: Color,
-    pub environment_map: Option<Vector2D<Color>>
+    pub environment_map: Option<Vector2D<Color>>,
+    // Multiplier on the environment map's radiance
+    pub environment_intensity: f64,
+    // Rotation of the environment map about the up axis, in degrees
+    pub environment_rotation: f64,
+    // Hash of the scene file this was loaded from, recorded in the metadata of rendered images
+    pub file_hash: Option<u64>
 }
 
 impl Scene {
@@ -18,7 +28,34 @@ impl Scene {
             meshes: meshes,
             texture_maps: albedo_maps,
             env_color: env_color,
-            environment_map: environment_map
+            environment_map: environment_map,
+            environment_intensity: 1.0,
+            environment_rotation: 0.0,
+            file_hash: None
         }
     }
+
+    pub fn with_environment_intensity(mut self, intensity: f64) -> Scene {
+        self.environment_intensity = intensity;
+        self
+    }
+
+    pub fn with_environment_rotation(mut self, rotation: f64) -> Scene {
+        self.environment_rotation = rotation;
+        self
+    }
+
+    pub fn with_file_hash(mut self, file_hash: u64) -> Scene {
+        self.file_hash = Some(file_hash);
+        self
+    }
+
+    pub fn from_file(path: &str) -> Result<Scene, SceneFileError> {
+        load_scene(path)
+    }
+
+    // Writes the triangles and materials to OBJ/MTL in the given convention, see obj_export
+    pub fn export_obj(&self, path: &str, options: &ImportOptions) -> Result<(), RTracerError> {
+        export_obj(self, path, options)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+use std::path::Path;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::datatypes::material::check_positive;
+use crate::error::RTracerError;
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Eye {
+    Left,
+    Right
+}
+
+impl Eye {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eye::Left => "left",
+            Eye::Right => "right"
+        }
+    }
+
+    // `path` with the eye's name before the extension, render.ckpt becomes render.left.ckpt
+    pub fn path(&self, path: &str) -> String {
+        let path: &Path = Path::new(path);
+        let stem: String = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        let name: String = match path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, self.name(), extension.to_string_lossy()),
+            None => format!("{}.{}", stem, self.name())
+        };
+        path.with_file_name(name).to_string_lossy().into_owned()
+    }
+}
+
+// Two views of the scene a human eye distance apart. Both eyes look straight ahead, parallel to the camera,
+// and their images are shifted so that objects `convergence_distance` away from the camera line up in the two
+// (an off-axis projection), which keeps the vertical parallax of toed-in cameras out of the pair.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stereo {
+    // World units between the two eyes, the camera position is halfway between them
+    pub interocular_distance: f64,
+    pub convergence_distance: f64
+}
+
+impl Stereo {
+    pub fn new(interocular_distance: f64, convergence_distance: f64) -> Stereo {
+        Stereo { interocular_distance, convergence_distance }
+    }
+
+    // Signed distance from the camera position to the eye along the camera's right axis
+    pub fn eye_offset(&self, eye: Eye) -> f64 {
+        match eye {
+            Eye::Left => -self.interocular_distance / 2.0,
+            Eye::Right => self.interocular_distance / 2.0
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), RTracerError> {
+        check_positive("interocular_distance", self.interocular_distance)?;
+        check_positive("convergence_distance", self.convergence_distance)
+    }
+}
+
+// How the two images of a stereo render are put into one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    // Left eye in the left half
+    SideBySide,
+    // Left eye in the top half
+    OverUnder,
+    // Red from the left eye, green and blue from the right, for red/cyan glasses
+    Anaglyph
+}
+
+pub struct StereoPair {
+    pub left: Vector2D<Color>,
+    pub right: Vector2D<Color>
+}
+
+impl StereoPair {
+    pub fn combine(&self, layout: StereoLayout) -> Vector2D<Color> {
+        let (width, height) = (self.left.width, self.left.height);
+        match layout {
+            StereoLayout::SideBySide => {
+                let mut image: Vector2D<Color> = Vector2D::new(2 * width, height, Color::black());
+                for row in 0..height {
+                    for col in 0..width {
+                        image.set(row, col, *self.left.get(row, col));
+                        image.set(row, width + col, *self.right.get(row, col));
+                    }
+                }
+                image
+            },
+            StereoLayout::OverUnder => {
+                let mut image: Vector2D<Color> = Vector2D::new(width, 2 * height, Color::black());
+                image.data[..width * height].copy_from_slice(&self.left.data);
+                image.data[width * height..].copy_from_slice(&self.right.data);
+                image
+            },
+            StereoLayout::Anaglyph => {
+                let mut image: Vector2D<Color> = self.right.clone();
+                for (color, left) in image.data.iter_mut().zip(&self.left.data) {
+                    color.red = left.red;
+                }
+                image
+            }
+        }
+    }
+}
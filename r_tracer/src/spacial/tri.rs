@@ -4,8 +4,13 @@ use crate::datatypes::hit_point::HitPoint;
 use crate::spacial::ray::Ray;
 use crate::spacial::bvh::BVH;
 use crate::datatypes::material::Material;
+use crate::datatypes::color::Color;
+use crate::spacial::motion::Motion;
 use rand::Rng;
 
+// Times a turning triangle's bounding box is taken at over the frame
+const MOTION_BOUND_STEPS: usize = 8;
+
 
 #[derive(Copy, Clone)]
 pub struct Tri {
@@ -18,6 +23,15 @@ pub struct Tri {
     pub p1_texture: Vector2,
     pub p2_texture: Vector2,
     pub p3_texture: Vector2,
+    pub p1_tangent: Vector3,
+    pub p2_tangent: Vector3,
+    pub p3_tangent: Vector3,
+    pub bitangent_sign: f64,
+    pub p1_color: Color,
+    pub p2_color: Color,
+    pub p3_color: Color,
+    // None for triangles that stay put
+    pub motion: Option<Motion>,
 
     pub normal: Vector3,
     pub smooth_shading: bool,
@@ -26,7 +40,12 @@ pub struct Tri {
     pub bounding_box_center: Vector3,
 
     pub material: Material,
-    pub is_empty: bool
+    pub is_empty: bool,
+
+    // Index of the owning MeshObject and of the material among the scene's distinct materials,
+    // assigned when the BVH is built
+    pub object_id: usize,
+    pub material_id: usize
 }
 
 impl Tri {
@@ -45,12 +64,22 @@ impl Tri {
             p1_texture: p1_texture,
             p2_texture: p2_texture,
             p3_texture: p3_texture,
+            p1_tangent: Vector3::zero(),
+            p2_tangent: Vector3::zero(),
+            p3_tangent: Vector3::zero(),
+            bitangent_sign: 1.0,
+            p1_color: Color::white(),
+            p2_color: Color::white(),
+            p3_color: Color::white(),
+            motion: None,
             normal: normal,
             material: material,
             is_empty: false,
             smooth_shading: false,
             bounding_box: bb,
-            bounding_box_center: Self::get_bounding_box_center(bb)
+            bounding_box_center: Self::get_bounding_box_center(bb),
+            object_id: 0,
+            material_id: 0
         }
     }
 
@@ -66,12 +95,86 @@ impl Tri {
             p1_texture: Vector2::zero(),
             p2_texture: Vector2::zero(),
             p3_texture: Vector2::zero(),
+            p1_tangent: Vector3::zero(),
+            p2_tangent: Vector3::zero(),
+            p3_tangent: Vector3::zero(),
+            bitangent_sign: 1.0,
+            p1_color: Color::white(),
+            p2_color: Color::white(),
+            p3_color: Color::white(),
+            motion: None,
             normal: Vector3::zero(),
             smooth_shading: false,
             is_empty: true,
             bounding_box: (Vector3::zero(), Vector3::zero()),
-            bounding_box_center: Vector3::zero()
+            bounding_box_center: Vector3::zero(),
+            object_id: 0,
+            material_id: 0
+        }
+    }
+
+    pub fn with_tangents(mut self, p1_tangent: Vector3, p2_tangent: Vector3,
+        p3_tangent: Vector3, bitangent_sign: f64) -> Tri {
+        self.p1_tangent = p1_tangent;
+        self.p2_tangent = p2_tangent;
+        self.p3_tangent = p3_tangent;
+        self.bitangent_sign = bitangent_sign;
+        self
+    }
+
+    pub fn with_vertex_colors(mut self, p1_color: Color, p2_color: Color, p3_color: Color) -> Tri {
+        self.p1_color = p1_color;
+        self.p2_color = p2_color;
+        self.p3_color = p3_color;
+        self
+    }
+
+    // Moves the triangle rigidly over the frame. The bounding box holds it at every time: pure translations
+    // are covered by the boxes at both ends, turning triangles by boxes at several times along the way, each
+    // widened by half the distance a vertex can travel to the next.
+    pub fn with_motion(mut self, motion: Motion) -> Tri {
+        let steps: usize = if motion.rotation == Vector3::zero() { 1 } else { MOTION_BOUND_STEPS };
+        let radius: f64 = [self.p1, self.p2, self.p3].iter()
+            .map(|&p: &Vector3| p.distance(motion.pivot)).fold(0.0, f64::max);
+        let padding: f64 = if steps == 1 { 0.0 } else { motion.path_length(radius) / (2.0 * steps as f64) };
+
+        let mut bounding_box: (Vector3, Vector3) = Self::get_bounding_box(self.p1, self.p2, self.p3);
+        for step in 1..=steps {
+            let time: f64 = step as f64 / steps as f64;
+            bounding_box = BVH::merge_bounding_boxes(bounding_box, Self::get_bounding_box(
+                motion.point_at(self.p1, time), motion.point_at(self.p2, time), motion.point_at(self.p3, time)
+            ));
         }
+        let padding: Vector3 = Vector3::new(padding, padding, padding);
+        self.bounding_box = (bounding_box.0 - padding, bounding_box.1 + padding);
+        self.bounding_box_center = Self::get_bounding_box_center(self.bounding_box);
+        self.motion = Some(motion);
+        self
+    }
+
+    pub fn has_tangents(&self) -> bool {
+        self.p1_tangent != Vector3::zero()
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    // Static copy of the triangle where it is at `time`
+    pub fn at_time(&self, time: f64) -> Tri {
+        let motion: Motion = match self.motion {
+            Some(motion) => motion,
+            None => return *self
+        };
+        let mut tri: Tri = *self;
+        let point = |p: Vector3| -> Vector3 { motion.point_at(p, time) };
+        let direction = |v: Vector3| -> Vector3 { motion.direction_at(v, time) };
+        (tri.p1, tri.p2, tri.p3) = (point(self.p1), point(self.p2), point(self.p3));
+        tri.normal = direction(self.normal);
+        (tri.p1_normal, tri.p2_normal, tri.p3_normal) = (direction(self.p1_normal), direction(self.p2_normal), direction(self.p3_normal));
+        (tri.p1_tangent, tri.p2_tangent, tri.p3_tangent) = (direction(self.p1_tangent), direction(self.p2_tangent), direction(self.p3_tangent));
+        tri.motion = None;
+        tri
     }
 
     pub fn get_bounding_box(p1: Vector3, p2: Vector3, p3: Vector3) -> (Vector3, Vector3) {
@@ -133,6 +236,9 @@ impl Tri {
     }
 
     pub fn intersect_tri(ray: &Ray, triangle: &Tri) -> HitPoint {
+        if triangle.is_moving() {
+            return Self::intersect_tri(ray, &triangle.at_time(ray.time))
+        }
 
         if triangle.normal*ray.direction > 0.0 {
             return HitPoint::empty()
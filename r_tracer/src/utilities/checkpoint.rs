@@ -0,0 +1,333 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::spacial::camera::Camera;
+use crate::error::RTracerError;
+
+
+const MAGIC: &[u8; 8] = b"RTCKPT\0\0";
+const CHECKPOINT_VERSION: u32 = 2;
+const HEADER_SIZE: usize = 44;
+
+// Where and how often a progressive render writes its checkpoint
+#[derive(Clone, Debug)]
+pub struct CheckpointSettings {
+    pub path: String,
+    pub interval: Duration
+}
+
+impl CheckpointSettings {
+    pub fn new(path: &str, interval: Duration) -> CheckpointSettings {
+        CheckpointSettings { path: path.to_string(), interval }
+    }
+}
+
+// The running mean of every pixel together with the number of samples it holds. Samples are seeded by
+// (seed, sample index, pixel), so carrying on from a checkpoint adds exactly the samples an uninterrupted
+// render would have added, and renders with different seeds hold independent samples that can be merged.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub seed: u64,
+    // Camera::fingerprint of the camera that rendered it, 0 when unknown
+    pub camera_hash: u64,
+    pub accumulation: Vector2D<Color>,
+    pub sample_counts: Vec<u32>
+}
+
+impl Checkpoint {
+    pub fn new(width: usize, height: usize, seed: u64) -> Checkpoint {
+        Checkpoint {
+            seed,
+            camera_hash: 0,
+            accumulation: Vector2D::new(width, height, Color::black()),
+            sample_counts: vec![0; width * height]
+        }
+    }
+
+    pub fn for_camera(camera: &Camera) -> Checkpoint {
+        let mut checkpoint: Checkpoint = Checkpoint::new(camera.width, camera.height, camera.seed);
+        checkpoint.camera_hash = camera.fingerprint();
+        checkpoint
+    }
+
+    pub fn width(&self) -> usize {
+        self.accumulation.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.accumulation.height
+    }
+
+    // Samples every pixel has, the first sample index a progressive pass still has to render
+    pub fn min_samples(&self) -> u32 {
+        self.sample_counts.iter().copied().min().unwrap_or(0)
+    }
+
+    // Folds sample number `sample_counts[index]` of a pixel into its mean with the same weights as the
+    // progressive accumulation, 1 / (n + 1) for the new sample
+    pub fn add_sample(&mut self, index: usize, color: Color) {
+        self.accumulation.data[index] = Self::accumulate(self.accumulation.data[index], self.sample_counts[index], color);
+        self.sample_counts[index] += 1;
+    }
+
+    pub fn accumulate(mean: Color, count: u32, color: Color) -> Color {
+        let weight: f64 = 1.0 / (count as f64 + 1.0);
+        mean * (1.0 - weight) + color * weight
+    }
+
+    // Sample count of every pixel as a grey image, for checking how evenly a merged render converged
+    pub fn sample_count_buffer(&self) -> Vector2D<Color> {
+        let mut buffer: Vector2D<Color> = Vector2D::new(self.width(), self.height(), Color::black());
+        for (pixel, &count) in buffer.data.iter_mut().zip(&self.sample_counts) {
+            *pixel = Color::new(count as f64, count as f64, count as f64);
+        }
+        buffer
+    }
+
+    // Whether `other` holds different samples of the same image
+    pub fn check_compatible(&self, other: &Checkpoint) -> Result<(), String> {
+        if (self.width(), self.height()) != (other.width(), other.height()) {
+            return Err(format!("resolution is {}x{} instead of {}x{}", other.width(), other.height(), self.width(), self.height()))
+        }
+        if self.camera_hash != other.camera_hash {
+            return Err(String::from("rendered with different camera or scene settings"))
+        }
+        if self.seed == other.seed {
+            return Err(format!("both renders use seed {} and hold the same samples", self.seed))
+        }
+        Ok(())
+    }
+
+    // Averages partial renders weighted by the samples each pixel holds. The result gets a seed of its own
+    // so that resuming it does not repeat the samples of any of the parts.
+    pub fn merge(parts: &[Checkpoint]) -> Result<Checkpoint, RTracerError> {
+        let first: &Checkpoint = parts.first()
+            .ok_or_else(|| RTracerError::IncompatibleRenders { message: String::from("nothing to merge") })?;
+        for (i, part) in parts.iter().enumerate().skip(1) {
+            for other in &parts[..i] {
+                other.check_compatible(part)
+                    .map_err(|message: String| RTracerError::IncompatibleRenders { message: format!("render {}: {}", i, message) })?;
+            }
+        }
+
+        let mut merged: Checkpoint = Checkpoint::new(first.width(), first.height(), merged_seed(parts));
+        merged.camera_hash = first.camera_hash;
+        for index in 0..merged.sample_counts.len() {
+            let count: u32 = parts.iter().map(|part: &Checkpoint| part.sample_counts[index]).sum();
+            if count == 0 {
+                continue
+            }
+            let mut sum: Color = Color::black();
+            for part in parts {
+                sum += part.accumulation.data[index] * part.sample_counts[index] as f64;
+            }
+            merged.accumulation.data[index] = sum * (1.0 / count as f64);
+            merged.sample_counts[index] = count;
+        }
+        Ok(merged)
+    }
+
+    // Loads and merges checkpoint files. A file that does not fit is reported as `render <i>`, its index in `paths`.
+    pub fn merge_files(paths: &[&str]) -> Result<Checkpoint, RTracerError> {
+        let parts: Vec<Checkpoint> = paths.iter().map(|path: &&str| Checkpoint::load(path)).collect::<Result<_, _>>()?;
+        Checkpoint::merge(&parts)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RTracerError> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.reserve(28 * self.sample_counts.len() + HEADER_SIZE);
+        bytes.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        for value in [self.width() as u64, self.height() as u64, self.seed, self.camera_hash] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for (count, color) in self.sample_counts.iter().zip(&self.accumulation.data) {
+            bytes.extend_from_slice(&count.to_le_bytes());
+            for value in [color.red, color.green, color.blue] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        // Replaces the previous checkpoint only once the new one is complete
+        let temporary: PathBuf = PathBuf::from(format!("{}.tmp", path));
+        fs::write(&temporary, bytes)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|err| RTracerError::io(path, err))
+    }
+
+    pub fn load(path: &str) -> Result<Checkpoint, RTracerError> {
+        let bytes: Vec<u8> = fs::read(path).map_err(|err| RTracerError::io(path, err))?;
+        let invalid = |message: &str| RTracerError::checkpoint(path, message);
+        if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+            return Err(invalid("not a checkpoint file"))
+        }
+
+        let u64_at = |offset: usize| -> u64 { u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap_or([0; 8])) };
+        let version: u32 = u32::from_le_bytes(bytes[8..12].try_into().unwrap_or([0; 4]));
+        if version != CHECKPOINT_VERSION {
+            return Err(invalid(&format!("written by checkpoint version {}, expected {}", version, CHECKPOINT_VERSION)))
+        }
+        let (width, height, seed) = (u64_at(12) as usize, u64_at(20) as usize, u64_at(28));
+        if width.checked_mul(height).and_then(|pixels: usize| pixels.checked_mul(28)) != Some(bytes.len() - HEADER_SIZE) {
+            return Err(invalid("file is truncated"))
+        }
+
+        let mut checkpoint: Checkpoint = Checkpoint::new(width, height, seed);
+        checkpoint.camera_hash = u64_at(36);
+        for (index, pixel) in bytes[HEADER_SIZE..].chunks_exact(28).enumerate() {
+            let f64_at = |offset: usize| -> f64 { f64::from_le_bytes(pixel[offset..offset + 8].try_into().unwrap_or([0; 8])) };
+            checkpoint.sample_counts[index] = u32::from_le_bytes(pixel[..4].try_into().unwrap_or([0; 4]));
+            checkpoint.accumulation.data[index] = Color::new(f64_at(4), f64_at(12), f64_at(20));
+        }
+        Ok(checkpoint)
+    }
+}
+
+// SplitMix64 over the seeds of the parts, a stream none of them used
+fn merged_seed(parts: &[Checkpoint]) -> u64 {
+    parts.iter().fold(0x9e3779b97f4a7c15, |hash: u64, part: &Checkpoint| {
+        let mut z: u64 = (hash ^ part.seed).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::material::Material;
+    use crate::datatypes::vector2::Vector2;
+    use crate::datatypes::vector3::Vector3;
+    use crate::spacial::bvh::BVH;
+    use crate::spacial::mesh_object::MeshObject;
+    use crate::spacial::scene::Scene;
+    use crate::spacial::tri::Tri;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("r_tracer_{}_{}.ckpt", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    // A grey wall half across the view in front of a white environment, so pixels see both and vary per sample
+    fn camera(seed: u64) -> Camera {
+        let material: Material = Material::builder().with_diffuse_color(Color::new(0.5, 0.5, 0.5)).build(0).unwrap();
+        let normal: Vector3 = Vector3::new(-1.0, 0.0, 0.0);
+        let (a, b, c, d) = (Vector3::new(5.0, -1.0, -4.0), Vector3::new(5.0, 4.0, -4.0),
+            Vector3::new(5.0, 4.0, 4.0), Vector3::new(5.0, -1.0, 4.0));
+        let uv: Vector2 = Vector2::zero();
+        let wall: MeshObject = MeshObject::new(vec![
+            Tri::new(a, b, c, normal, normal, normal, normal, uv, uv, uv, material),
+            Tri::new(a, c, d, normal, normal, normal, normal, uv, uv, uv, material)
+        ], false);
+        let scene: Scene = Scene::new(vec![wall], vec![], Color::white(), Some(Vector2D::new(2, 1, Color::white())));
+        Camera::builder(scene, 6, 4).with_max_bounces(2).with_blur_strength(0.5).with_seed(seed).build().unwrap()
+    }
+
+    fn checkpoint(seed: u64, colors: &[Color], sample_counts: &[u32]) -> Checkpoint {
+        let mut checkpoint: Checkpoint = Checkpoint::new(colors.len(), 1, seed);
+        checkpoint.accumulation.data = colors.to_vec();
+        checkpoint.sample_counts = sample_counts.to_vec();
+        checkpoint
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        let camera: Camera = camera(7);
+        let bvh: BVH = camera.build_bvh();
+
+        let mut straight: Checkpoint = Checkpoint::for_camera(&camera);
+        camera.render_progressive(&bvh, &mut straight, 6, |_| ());
+
+        let path: String = temp_path("resume");
+        let mut first_half: Checkpoint = Checkpoint::for_camera(&camera);
+        camera.render_progressive(&bvh, &mut first_half, 3, |_| ());
+        first_half.save(&path).unwrap();
+        let mut resumed: Checkpoint = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        camera.render_progressive(&bvh, &mut resumed, 6, |_| ());
+
+        assert!(straight.sample_counts.iter().all(|&count: &u32| count == 6));
+        assert!(resumed.sample_counts == straight.sample_counts);
+        assert!(resumed.accumulation.data == straight.accumulation.data);
+        // The samples differ, otherwise the comparison above proves nothing
+        assert!(straight.accumulation.data != first_half.accumulation.data);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut saved: Checkpoint = checkpoint(42, &[Color::new(0.25, 1.5, 3.0), Color::black(), Color::new(1e-9, 2.0, 0.5)], &[3, 0, 12]);
+        saved.camera_hash = 0xdead_beef_0123_4567;
+        let path: String = temp_path("round_trip");
+        saved.save(&path).unwrap();
+        let loaded: Checkpoint = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((loaded.width(), loaded.height()), (3, 1));
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.camera_hash, saved.camera_hash);
+        assert_eq!(loaded.sample_counts, saved.sample_counts);
+        assert!(loaded.accumulation.data == saved.accumulation.data);
+    }
+
+    #[test]
+    fn load_rejects_truncated_files() {
+        let path: String = temp_path("truncated");
+        checkpoint(1, &[Color::white(); 4], &[1; 4]).save(&path).unwrap();
+        let bytes: Vec<u8> = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let result: Result<Checkpoint, RTracerError> = Checkpoint::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RTracerError::Checkpoint { .. })));
+    }
+
+    #[test]
+    fn merge_weights_pixels_by_sample_count() {
+        let a: Checkpoint = checkpoint(1, &[Color::new(1.0, 1.0, 1.0), Color::new(0.2, 0.4, 0.6), Color::black()], &[3, 2, 0]);
+        let b: Checkpoint = checkpoint(2, &[Color::new(5.0, 5.0, 5.0), Color::black(), Color::black()], &[1, 0, 0]);
+        let merged: Checkpoint = Checkpoint::merge(&[a.clone(), b.clone()]).unwrap();
+
+        assert_eq!(merged.sample_counts, vec![4, 2, 0]);
+        // (3 * 1 + 1 * 5) / 4, a pixel only one part sampled keeps its mean, an unsampled one stays black
+        assert!(merged.accumulation.data == vec![Color::new(2.0, 2.0, 2.0), Color::new(0.2, 0.4, 0.6), Color::black()]);
+        assert!(merged.seed != a.seed && merged.seed != b.seed);
+    }
+
+    #[test]
+    fn merge_rejects_incompatible_renders() {
+        let colors: [Color; 2] = [Color::white(); 2];
+        let base: Checkpoint = checkpoint(1, &colors, &[1, 1]);
+        let mut other_camera: Checkpoint = checkpoint(2, &colors, &[1, 1]);
+        other_camera.camera_hash = 99;
+        let cases: [Vec<Checkpoint>; 4] = [
+            vec![],
+            vec![base.clone(), checkpoint(2, &[Color::white(); 3], &[1, 1, 1])],
+            vec![base.clone(), other_camera],
+            vec![base.clone(), checkpoint(1, &colors, &[1, 1])]
+        ];
+        for parts in cases {
+            assert!(matches!(Checkpoint::merge(&parts), Err(RTracerError::IncompatibleRenders { .. })));
+        }
+    }
+
+    #[test]
+    fn merge_files_names_the_file_that_does_not_fit() {
+        let colors: [Color; 2] = [Color::white(); 2];
+        let paths: [String; 3] = [temp_path("merge_a"), temp_path("merge_b"), temp_path("merge_c")];
+        checkpoint(1, &colors, &[1, 1]).save(&paths[0]).unwrap();
+        checkpoint(2, &colors, &[1, 1]).save(&paths[1]).unwrap();
+        checkpoint(1, &colors, &[1, 1]).save(&paths[2]).unwrap();
+
+        let path_refs: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let merged: Result<Checkpoint, RTracerError> = Checkpoint::merge_files(&path_refs[..2]);
+        let mismatched: Result<Checkpoint, RTracerError> = Checkpoint::merge_files(&path_refs);
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(merged.unwrap().sample_counts, vec![2, 2]);
+        match mismatched {
+            Err(RTracerError::IncompatibleRenders { message }) => assert!(message.starts_with("render 2:"), "{}", message),
+            _ => panic!("expected the third file to be rejected")
+        }
+    }
+}
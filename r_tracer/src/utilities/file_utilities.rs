@@ -1,152 +1,700 @@
 use pk_stl::parse_stl;
-use obj::{load_obj, Obj, TexturedVertex};
-use image::{Rgb, RgbImage, DynamicImage, ImageError};
-use rayon::prelude::*;
+use obj::raw::{parse_obj, RawObj};
+use obj::raw::object::Polygon;
+use image::{Rgb, RgbImage, Rgb32FImage, DynamicImage, ImageBuffer, ImageError};
+use exr::prelude::{f16, Encoding, Image, Layer, LayerAttributes, SpecificChannels, Vec2, WritableImage};
+use image::codecs::hdr::{HdrDecoder, HdrMetadata};
 use crate::spacial::tri::Tri;
+use crate::spacial::mesh_object::MeshObject;
+use crate::utilities::gltf_utilities::import_gltf;
+use crate::utilities::vertex_welding::{compute_vertex_normals, WeldOptions};
+use crate::utilities::import_options::ImportOptions;
 use crate::datatypes::material::Material;
 use crate::datatypes::vector3::Vector3;
 use crate::datatypes::vector2::Vector2;
 use crate::datatypes::color::Color;
 use crate::datatypes::vector2d::Vector2D;
-use crate::utilities::postprocessing::remove_fireflies;
+use crate::error::RTracerError;
+use crate::utilities::postprocessing::PostProcessing;
+use crate::utilities::render_metadata::RenderMetadata;
 use std::fs;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
 
-pub fn load_model(file_path: &str, material: Material) -> Vec<Tri> {
+pub fn load_model(file_path: &str, material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
     if file_path.ends_with(".obj") {
         println!("Processing .obj file: {}", file_path);
-        return import_obj(file_path, material)
+        return import_obj(file_path, material, options)
     } else if file_path.ends_with(".stl") {
         println!("Processing .stl file: {}", file_path);
-        return import_stl(file_path, material)
+        return import_stl(file_path, material, options, &WeldOptions::default())
+    } else if file_path.ends_with(".ply") {
+        println!("Processing .ply file: {}", file_path);
+        return import_ply(file_path, material, options)
+    } else if file_path.ends_with(".gltf") || file_path.ends_with(".glb") {
+        println!("Processing glTF file: {}", file_path);
+        let (meshes, _) = import_gltf(file_path, &mut vec![], options)?;
+        return Ok(meshes.into_iter()
+            .flat_map(|mesh: MeshObject| mesh.tris)
+            .map(|mut tri: Tri| { tri.material = material; tri })
+            .collect())
     } else {
-        println!("Unsupported file extension");
-        return vec![]
+        return Err(RTracerError::UnsupportedFormat { path: file_path.to_string() })
     }
 }
 
-fn import_stl(file_path: &str, material: Material) -> Vec<Tri> {
-    let content = fs::read(file_path).expect("Failed to read model file");
-    let model = parse_stl(content.as_slice()).unwrap();
+pub fn load_model_with_materials(file_path: &str, texture_maps: &mut Vec<Vector2D<Color>>,
+    default_material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+    if file_path.ends_with(".obj") {
+        println!("Processing .obj file: {}", file_path);
+        import_obj_with_materials(file_path, texture_maps, default_material, options)
+    } else if file_path.ends_with(".gltf") || file_path.ends_with(".glb") {
+        println!("Processing glTF file: {}", file_path);
+        let (meshes, _) = import_gltf(file_path, texture_maps, options)?;
+        Ok(meshes.into_iter().flat_map(|mesh: MeshObject| mesh.tris).collect())
+    } else {
+        load_model(file_path, default_material, options)
+    }
+}
+
+pub fn load_stl(file_path: &str, material: Material, options: &ImportOptions,
+    weld_options: &WeldOptions) -> Result<Vec<Tri>, RTracerError> {
+    println!("Processing .stl file: {}", file_path);
+    import_stl(file_path, material, options, weld_options)
+}
+
+fn import_stl(file_path: &str, material: Material, options: &ImportOptions,
+    weld_options: &WeldOptions) -> Result<Vec<Tri>, RTracerError> {
+    let content: Vec<u8> = fs::read(file_path).map_err(|err| RTracerError::io(file_path, err))?;
+    let model = parse_stl(content.as_slice()).map_err(|err| RTracerError::model_parse(file_path, err))?;
+
+    let positions: Vec<[Vector3; 3]> = model.triangles.iter().map(|tri| {
+        tri.vertices.map(|v| options.convert_point(v.x.into(), v.y.into(), v.z.into()))
+    }).collect();
+    let face_normals: Vec<Vector3> = model.triangles.iter().zip(&positions).map(|(tri, p)| {
+        let face_normal: Vector3 = options.convert_normal(tri.normal.x.into(), tri.normal.y.into(), tri.normal.z.into());
+        // Some exporters leave the stored normal zeroed, fall back to the winding order
+        if face_normal == Vector3::zero() {
+            options.face_normal(p[0], p[1], p[2])
+        } else {
+            face_normal.normalize()
+        }
+    }).collect();
 
     println!("Computing vertex normals for {}", file_path);
-    let counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
-    let model_tris: Vec<Tri> = model.triangles.par_iter().map(|tri| {
-        let vertex1: Vector3 = Vector3::new(tri.vertices[0].x.into(), tri.vertices[0].y.into(), tri.vertices[0].z.into());
-        let vertex2: Vector3 = Vector3::new(tri.vertices[1].x.into(), tri.vertices[1].y.into(), tri.vertices[1].z.into());
-        let vertex3: Vector3 = Vector3::new(tri.vertices[2].x.into(), tri.vertices[2].y.into(), tri.vertices[2].z.into());
-        let face_normal = Vector3::new(tri.normal.x.into(), tri.normal.y.into(), tri.normal.z.into());
-    
-        let mut vertex1_normal: Vector3 = Vector3::zero();
-        let mut vertex2_normal: Vector3 = Vector3::zero();
-        let mut vertex3_normal: Vector3 = Vector3::zero();
-    
-        let e: f64 = 0.0001;
-        for other_tri in &model.triangles {
-            let other_vertex1: Vector3 = Vector3::new(other_tri.vertices[0].x.into(), other_tri.vertices[0].y.into(), other_tri.vertices[0].z.into());
-            let other_vertex2: Vector3 = Vector3::new(other_tri.vertices[1].x.into(), other_tri.vertices[1].y.into(), other_tri.vertices[1].z.into());
-            let other_vertex3: Vector3 = Vector3::new(other_tri.vertices[2].x.into(), other_tri.vertices[2].y.into(), other_tri.vertices[2].z.into());
-            let other_face_normal = Vector3::new(other_tri.normal.x.into(), other_tri.normal.y.into(), other_tri.normal.z.into());
-    
-            if (vertex1 - other_vertex1).magnitude() < e
-                || (vertex1 - other_vertex2).magnitude() < e
-                || (vertex1 - other_vertex3).magnitude() < e
-            {
-                vertex1_normal += other_face_normal;
+    let vertex_normals: Vec<[Vector3; 3]> = compute_vertex_normals(&positions, &face_normals, weld_options);
+
+    let model_tris: Vec<Tri> = positions.iter().zip(&face_normals).zip(&vertex_normals)
+        .map(|((p, &face_normal), n)| Tri::new(
+            p[0], p[1], p[2], n[0], n[1], n[2], face_normal,
+            Vector2::zero(), Vector2::zero(), Vector2::zero(), material
+        )).collect();
+    println!("Done");
+    Ok(model_tris)
+}
+
+fn import_obj(file_path: &str, material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+    let raw_obj: RawObj = read_raw_obj(file_path)?;
+    let polygon_materials: Vec<Material> = vec![material; raw_obj.polygons.len()];
+    Ok(build_obj_tris(&raw_obj, &polygon_materials, options))
+}
+
+fn import_obj_with_materials(file_path: &str, texture_maps: &mut Vec<Vector2D<Color>>,
+    default_material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+    let raw_obj: RawObj = read_raw_obj(file_path)?;
+    let obj_dir: &Path = Path::new(file_path).parent().unwrap_or(Path::new(""));
+
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    for library in &raw_obj.material_libraries {
+        let mtl_path: PathBuf = obj_dir.join(library);
+        println!("Loading material library: {}", mtl_path.display());
+        let mtl_path_str: String = mtl_path.to_string_lossy().into_owned();
+        let content: String = fs::read_to_string(&mtl_path).map_err(|err| RTracerError::io(&mtl_path_str, err))?;
+        materials.extend(parse_mtl(&content, mtl_path.parent().unwrap_or(obj_dir), texture_maps)?);
+    }
+
+    let mut polygon_materials: Vec<Material> = vec![default_material; raw_obj.polygons.len()];
+    for (name, group) in &raw_obj.meshes {
+        let material: Material = match materials.get(name) {
+            Some(&material) => material,
+            None => {
+                if !name.is_empty() {
+                    eprintln!("Material '{}' not found, using the default material", name);
+                }
+                continue
             }
-            if (vertex2 - other_vertex1).magnitude() < e
-                || (vertex2 - other_vertex2).magnitude() < e
-                || (vertex2 - other_vertex3).magnitude() < e
-            {
-                vertex2_normal += other_face_normal;
+        };
+        for range in &group.polygons {
+            for polygon_material in &mut polygon_materials[range.start..range.end] {
+                *polygon_material = material;
             }
-            if (vertex3 - other_vertex1).magnitude() < e
-                || (vertex3 - other_vertex2).magnitude() < e
-                || (vertex3 - other_vertex3).magnitude() < e
-            {
-                vertex3_normal += other_face_normal;
+        }
+    }
+
+    Ok(build_obj_tris(&raw_obj, &polygon_materials, options))
+}
+
+fn read_raw_obj(file_path: &str) -> Result<RawObj, RTracerError> {
+    let input = BufReader::new(File::open(file_path).map_err(|err| RTracerError::io(file_path, err))?);
+    parse_obj(input).map_err(|err| RTracerError::model_parse(file_path, err))
+}
+
+fn build_obj_tris(raw_obj: &RawObj, polygon_materials: &[Material], options: &ImportOptions) -> Vec<Tri> {
+    let position = |i: usize| -> Vector3 {
+        let p = raw_obj.positions[i];
+        options.convert_point(p.0 as f64, p.1 as f64, p.2 as f64)
+    };
+    let normal = |i: usize| -> Vector3 {
+        let n = raw_obj.normals[i];
+        options.convert_normal(n.0 as f64, n.1 as f64, n.2 as f64)
+    };
+    let texture = |i: usize| -> Vector2 {
+        let t = raw_obj.tex_coords[i];
+        options.convert_uv(t.0 as f64, t.1 as f64)
+    };
+
+    let mut triangles: Vec<Tri> = Vec::new();
+    for (polygon, &material) in raw_obj.polygons.iter().zip(polygon_materials) {
+        // (position, normal, texture coordinate) per corner
+        let corners: Vec<(Vector3, Option<Vector3>, Vector2)> = match polygon {
+            Polygon::P(vec) => vec.iter()
+                .map(|&p| (position(p), None, Vector2::zero())).collect(),
+            Polygon::PT(vec) => vec.iter()
+                .map(|&(p, t)| (position(p), None, texture(t))).collect(),
+            Polygon::PN(vec) => vec.iter()
+                .map(|&(p, n)| (position(p), Some(normal(n)), Vector2::zero())).collect(),
+            Polygon::PTN(vec) => vec.iter()
+                .map(|&(p, t, n)| (position(p), Some(normal(n)), texture(t))).collect()
+        };
+
+        // Triangulate n-gons as a fan around the first corner
+        for i in 1..corners.len() - 1 {
+            let (p1, n1, t1) = corners[0];
+            let (p2, n2, t2) = corners[i];
+            let (p3, n3, t3) = corners[i + 1];
+            let face_normal: Vector3 = options.face_normal(p1, p2, p3);
+
+            triangles.push(Tri::new(
+                p1, p2, p3,
+                n1.unwrap_or(face_normal), n2.unwrap_or(face_normal), n3.unwrap_or(face_normal),
+                face_normal, t1, t2, t3, material
+            ));
+        }
+    }
+
+    triangles
+}
+
+// Statements parse_mtl loads a texture for
+const MTL_MAP_STATEMENTS: [&str; 8] = ["map_Kd", "map_Ks", "map_Ke", "map_Ns", "map_Bump", "map_bump", "bump", "norm"];
+
+pub fn parse_mtl(content: &str, texture_dir: &Path, texture_maps: &mut Vec<Vector2D<Color>>)
+    -> Result<HashMap<String, Material>, RTracerError> {
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut loaded_textures: HashMap<PathBuf, usize> = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for line in content.lines() {
+        let line: &str = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let statement: &str = match tokens.next() {
+            Some(statement) => statement,
+            None => continue
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if statement == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.to_material());
             }
+            current = Some((args.join(" "), MtlMaterial::new()));
+            continue
         }
-    
-        vertex1_normal = vertex1_normal.normalize();
-        vertex2_normal = vertex2_normal.normalize();
-        vertex3_normal = vertex3_normal.normalize();
-    
-        let tri: Tri = Tri::new(
-            vertex1, vertex2, vertex3, vertex1_normal, 
-            vertex2_normal, vertex3_normal, face_normal,
-            Vector2::zero(), Vector2::zero(), 
-            Vector2::zero(), material
-        );
-
-        let current: usize = counter.fetch_add(1, Ordering::Relaxed);
-        if current % 1000 == 0 || model.triangles.len() == current {
-            println!("Computing vertex normals: {}/{}", current, model.triangles.len());
-        }
-        tri
-    }).collect();
-    println!("Done");
-    model_tris
+
+        let mtl: &mut MtlMaterial = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None => continue
+        };
+
+        let mut load_map = |args: &[&str]| -> Result<Option<usize>, RTracerError> {
+            let file: &str = match split_map_args(args) {
+                Some((file, _)) => file,
+                None => return Ok(None)
+            };
+            let texture_path: PathBuf = texture_dir.join(file);
+            if let Some(&index) = loaded_textures.get(&texture_path) {
+                return Ok(Some(index))
+            }
+            let index: usize = texture_maps.len();
+            texture_maps.push(import_texture(&texture_path.to_string_lossy())?);
+            loaded_textures.insert(texture_path, index);
+            Ok(Some(index))
+        };
+
+        match statement {
+            "Kd" => mtl.diffuse = parse_mtl_color(&args),
+            "Ks" => mtl.specular = parse_mtl_color(&args),
+            "Ke" => mtl.emissive = parse_mtl_color(&args),
+            "Tf" => mtl.transmission_filter = parse_mtl_color(&args),
+            "Ns" => mtl.specular_exponent = parse_mtl_float(&args),
+            "Ni" => mtl.optical_density = parse_mtl_float(&args),
+            "d" => mtl.dissolve = parse_mtl_float(&args),
+            "Tr" => mtl.dissolve = parse_mtl_float(&args).map(|tr: f64| 1.0 - tr),
+            "Pr" => mtl.roughness = parse_mtl_float(&args),
+            "Pm" => mtl.metallic = parse_mtl_float(&args),
+            "illum" => mtl.illumination_model = parse_mtl_float(&args).map(|illum: f64| illum as u32),
+            // Keep MTL_MAP_STATEMENTS in sync, the mesh cache hashes the files these load
+            "map_Kd" => mtl.diffuse_map = load_map(&args)?,
+            "map_Ks" => mtl.specular_map = load_map(&args)?,
+            "map_Ke" => mtl.emissive_map = load_map(&args)?,
+            "map_Ns" => mtl.specular_exponent_map = load_map(&args)?,
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                mtl.bump_map = load_map(&args)?;
+                mtl.bump_multiplier = split_map_args(&args).and_then(|(_, bm)| bm);
+            },
+            _ => {}
+        }
+    }
+
+    if let Some((name, mtl)) = current.take() {
+        materials.insert(name, mtl.to_material());
+    }
+
+    Ok(materials)
 }
 
-fn import_obj(file_path: &str, material: Material) -> Vec<Tri> {
-    let input = BufReader::new(File::open(file_path).expect("Failed to read OBJ"));
-    let dome:  Obj<TexturedVertex> = load_obj(input).expect("Failed to load OBJ");
-    let vertices = dome.vertices;
-    let indices = dome.indices;
+// Texture files an MTL file refers to, resolved like parse_mtl resolves them
+pub(crate) fn mtl_texture_files(content: &str, texture_dir: &Path) -> Vec<PathBuf> {
+    content.lines()
+        .filter_map(|line: &str| {
+            let args: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+            match args.split_first() {
+                Some((statement, args)) if MTL_MAP_STATEMENTS.contains(statement) => split_map_args(args),
+                _ => None
+            }
+        })
+        .map(|(file, _)| texture_dir.join(file))
+        .collect()
+}
+
+// Texture map statements may carry options before the file name (e.g. `map_Bump -bm 0.5 normal.png`).
+// Only the bump multiplier is used, everything else is skipped.
+fn split_map_args<'a>(args: &[&'a str]) -> Option<(&'a str, Option<f64>)> {
+    let file: &str = args.last()?;
+    let bump_multiplier: Option<f64> = args.iter().position(|&arg| arg == "-bm")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|value| value.parse().ok());
+    Some((file, bump_multiplier))
+}
+
+fn parse_mtl_color(args: &[&str]) -> Option<Color> {
+    let values: Vec<f64> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+    match values[..] {
+        [v] => Some(Color::new(v, v, v)),
+        [r, g, b, ..] => Some(Color::new(r, g, b)),
+        _ => None
+    }
+}
+
+fn parse_mtl_float(args: &[&str]) -> Option<f64> {
+    args.first().and_then(|arg| arg.parse().ok())
+}
+
+struct MtlMaterial {
+    diffuse: Option<Color>,
+    specular: Option<Color>,
+    emissive: Option<Color>,
+    transmission_filter: Option<Color>,
+    specular_exponent: Option<f64>,
+    optical_density: Option<f64>,
+    dissolve: Option<f64>,
+    roughness: Option<f64>,
+    metallic: Option<f64>,
+    illumination_model: Option<u32>,
+    diffuse_map: Option<usize>,
+    specular_map: Option<usize>,
+    emissive_map: Option<usize>,
+    specular_exponent_map: Option<usize>,
+    bump_map: Option<usize>,
+    bump_multiplier: Option<f64>
+}
+
+impl MtlMaterial {
+    fn new() -> MtlMaterial {
+        MtlMaterial {
+            diffuse: None, specular: None, emissive: None, transmission_filter: None,
+            specular_exponent: None, optical_density: None, dissolve: None,
+            roughness: None, metallic: None, illumination_model: None,
+            diffuse_map: None, specular_map: None, emissive_map: None,
+            specular_exponent_map: None, bump_map: None, bump_multiplier: None
+        }
+    }
+
+    fn to_material(&self) -> Material {
+        let diffuse_color: Color = self.diffuse.unwrap_or(Color::white() * 0.8);
+        let emission_color: Color = self.emissive.unwrap_or(Color::black());
+        let mut specular_color: Color = self.specular.unwrap_or(Color::white());
+
+        // Ns runs from 0 to 1000, Pr (PBR extension) takes precedence when present
+        let smoothness: f64 = match (self.roughness, self.specular_exponent) {
+            (Some(roughness), _) => 1.0 - roughness.clamp(0.0, 1.0),
+            (None, Some(exponent)) => (exponent / 1000.0).clamp(0.0, 1.0).sqrt(),
+            (None, None) => 0.0
+        };
+
+        // Metals tint their reflections with the base color
+        let mut specular: f64 = match self.metallic {
+            Some(metallic) => {
+                specular_color = Color::lerp(specular_color, diffuse_color, metallic.clamp(0.0, 1.0));
+                metallic.clamp(0.0, 1.0)
+            },
+            None => self.specular.map_or(0.0, |color: Color| color.to_greyscale().clamp(0.0, 1.0))
+        };
+        // illum 0 and 1 have no specular highlights
+        if let Some(0) | Some(1) = self.illumination_model {
+            specular = 0.0;
+        }
+
+        let dielectric: f64 = 1.0 - self.dissolve.unwrap_or(1.0).clamp(0.0, 1.0);
+        let emission_strength: f64 = if emission_color != Color::black() || self.emissive_map.is_some() { 1.0 } else { 0.0 };
+
+        Material::new(
+            diffuse_color, emission_color, specular_color,
+            self.transmission_filter.unwrap_or(Color::white()),
+            emission_strength, smoothness, specular, dielectric,
+            self.optical_density.unwrap_or(1.5), self.bump_multiplier.unwrap_or(1.0), true,
+            self.diffuse_map, self.emissive_map, self.specular_map, None,
+            self.bump_map, self.specular_exponent_map, None
+        )
+    }
+}
+
+fn import_ply(file_path: &str, material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+    let content: Vec<u8> = fs::read(file_path).map_err(|err| RTracerError::io(file_path, err))?;
+    let ply: PlyData = parse_ply(&content).map_err(|err: String| RTracerError::model_parse(file_path, err))?;
+
+    let positions: Vec<Vector3> = ply.positions.iter()
+        .map(|p: &Vector3| options.convert_point(p.x, p.y, p.z))
+        .collect();
+    let tex_coords: Option<Vec<Vector2>> = ply.tex_coords.as_ref().map(|tex_coords: &Vec<Vector2>| tex_coords.iter()
+        .map(|t: &Vector2| options.convert_uv(t.x, t.y))
+        .collect());
+    let vertex_normals: Vec<Vector3> = match &ply.normals {
+        Some(normals) => normals.iter().map(|n: &Vector3| options.convert_normal(n.x, n.y, n.z)).collect(),
+        None => compute_indexed_vertex_normals(&positions, &ply.faces, options)
+    };
+
     let mut triangles: Vec<Tri> = Vec::new();
+    for face in &ply.faces {
+        // Triangulate n-gons as a fan around the first corner
+        for i in 1..face.len() - 1 {
+            let (i1, i2, i3) = (face[0], face[i], face[i + 1]);
+            let (p1, p2, p3) = (positions[i1], positions[i2], positions[i3]);
+            let texture = |index: usize| -> Vector2 {
+                tex_coords.as_ref().map_or(Vector2::zero(), |tex_coords: &Vec<Vector2>| tex_coords[index])
+            };
 
-    for chunk in indices.chunks(3) {
-        if chunk.len() != 3 {
-            continue;
+            let mut tri: Tri = Tri::new(
+                p1, p2, p3,
+                vertex_normals[i1], vertex_normals[i2], vertex_normals[i3],
+                options.face_normal(p1, p2, p3),
+                texture(i1), texture(i2), texture(i3), material
+            );
+            if let Some(colors) = &ply.colors {
+                tri = tri.with_vertex_colors(colors[i1], colors[i2], colors[i3]);
+            }
+            triangles.push(tri);
         }
+    }
 
-        let p1_index = chunk[0] as usize;
-        let p2_index = chunk[1] as usize;
-        let p3_index = chunk[2] as usize;
+    Ok(triangles)
+}
 
-        let vert1 = vertices[p1_index];
-        let vert2 = vertices[p2_index];
-        let vert3 = vertices[p3_index];
+fn compute_indexed_vertex_normals(positions: &[Vector3], faces: &[Vec<usize>], options: &ImportOptions) -> Vec<Vector3> {
+    let mut normals: Vec<Vector3> = vec![Vector3::zero(); positions.len()];
+    for face in faces {
+        for i in 1..face.len() - 1 {
+            let (i1, i2, i3) = (face[0], face[i], face[i + 1]);
+            let face_normal: Vector3 = options.face_normal(positions[i1], positions[i2], positions[i3]);
+            normals[i1] += face_normal;
+            normals[i2] += face_normal;
+            normals[i3] += face_normal;
+        }
+    }
+    normals.into_iter().map(|normal: Vector3| normal.normalize()).collect()
+}
 
-        let p1: Vector3 = Vector3::new(vert1.position[0] as f64, vert1.position[2] as f64, vert1.position[1] as f64);
-        let p2: Vector3 = Vector3::new(vert2.position[0] as f64, vert2.position[2] as f64, vert2.position[1] as f64);
-        let p3: Vector3 = Vector3::new(vert3.position[0] as f64, vert3.position[2] as f64, vert3.position[1] as f64);
+pub struct PlyData {
+    pub positions: Vec<Vector3>,
+    pub normals: Option<Vec<Vector3>>,
+    pub tex_coords: Option<Vec<Vector2>>,
+    pub colors: Option<Vec<Color>>,
+    pub faces: Vec<Vec<usize>>
+}
 
-        let p1_normal: Vector3 = Vector3::new(vert1.normal[0] as f64, vert1.normal[2] as f64, vert1.normal[1] as f64);
-        let p2_normal: Vector3 = Vector3::new(vert2.normal[0] as f64, vert2.normal[2] as f64, vert2.normal[1] as f64);
-        let p3_normal: Vector3 = Vector3::new(vert3.normal[0] as f64, vert3.normal[2] as f64, vert3.normal[1] as f64);
+#[derive(PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
 
-        let p1_texture: Vector2 = Vector2::new(vert1.texture[0] as f64, vert1.texture[1] as f64);
-        let p2_texture: Vector2 = Vector2::new(vert2.texture[0] as f64, vert2.texture[1] as f64);
-        let p3_texture: Vector2 = Vector2::new(vert3.texture[0] as f64, vert3.texture[1] as f64);
+#[derive(Copy, Clone)]
+enum PlyScalar { I8, U8, I16, U16, I32, U32, F32, F64 }
 
+impl PlyScalar {
+    fn parse(name: &str) -> Result<PlyScalar, String> {
+        match name {
+            "char" | "int8" => Ok(PlyScalar::I8),
+            "uchar" | "uint8" => Ok(PlyScalar::U8),
+            "short" | "int16" => Ok(PlyScalar::I16),
+            "ushort" | "uint16" => Ok(PlyScalar::U16),
+            "int" | "int32" => Ok(PlyScalar::I32),
+            "uint" | "uint32" => Ok(PlyScalar::U32),
+            "float" | "float32" => Ok(PlyScalar::F32),
+            "double" | "float64" => Ok(PlyScalar::F64),
+            _ => Err(format!("unknown property type '{}'", name))
+        }
+    }
 
-        let triangle = Tri::new(p1, p2, p3, p1_normal, p2_normal, p3_normal,
-            Tri::compute_face_normal(p1, p2, p3), 
-            p1_texture, p2_texture, p3_texture, material
-        );
+    fn size(self) -> usize {
+        match self {
+            PlyScalar::I8 | PlyScalar::U8 => 1,
+            PlyScalar::I16 | PlyScalar::U16 => 2,
+            PlyScalar::I32 | PlyScalar::U32 | PlyScalar::F32 => 4,
+            PlyScalar::F64 => 8
+        }
+    }
 
-        triangles.push(triangle);
+    // Scale used to bring integer colors into [0, 1]
+    fn color_scale(self) -> f64 {
+        match self {
+            PlyScalar::U8 | PlyScalar::I8 => 255.0,
+            PlyScalar::U16 | PlyScalar::I16 => 65535.0,
+            _ => 1.0
+        }
     }
+}
 
+struct PlyProperty {
+    name: String,
+    scalar: PlyScalar,
+    // Some(count type) for list properties
+    list_count: Option<PlyScalar>
+}
 
-    triangles
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>
+}
+
+struct PlyReader<'a> {
+    format: PlyFormat,
+    body: &'a [u8],
+    position: usize
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, scalar: PlyScalar) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            return self.read_ascii_token()?.parse::<f64>().map_err(|err| err.to_string())
+        }
+
+        let size: usize = scalar.size();
+        let bytes: &[u8] = self.body.get(self.position..self.position + size)
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        self.position += size;
+        let mut buffer: [u8; 8] = [0; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+
+        Ok(match scalar {
+            PlyScalar::I8 => buffer[0] as i8 as f64,
+            PlyScalar::U8 => buffer[0] as f64,
+            PlyScalar::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            PlyScalar::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            PlyScalar::I32 => i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            PlyScalar::U32 => u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            PlyScalar::F32 => f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            PlyScalar::F64 => f64::from_le_bytes(buffer)
+        })
+    }
+
+    fn read_ascii_token(&mut self) -> Result<&'a str, String> {
+        while self.position < self.body.len() && self.body[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start: usize = self.position;
+        while self.position < self.body.len() && !self.body[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err("unexpected end of file".to_string())
+        }
+        std::str::from_utf8(&self.body[start..self.position]).map_err(|err| err.to_string())
+    }
 }
 
-pub fn import_texture(path: &str) -> Vector2D<Color> {
+pub fn parse_ply(content: &[u8]) -> Result<PlyData, String> {
+    let header_end: usize = content.windows(10).position(|window: &[u8]| window == b"end_header")
+        .ok_or_else(|| "missing end_header".to_string())?;
+    let header: &str = std::str::from_utf8(&content[..header_end]).map_err(|err| err.to_string())?;
+    // The body starts after the line break following end_header
+    let body_start: usize = content[header_end..].iter().position(|&byte: &u8| byte == b'\n')
+        .map_or(content.len(), |offset: usize| header_end + offset + 1);
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("not a PLY file".to_string())
+    }
+
+    let mut format: Option<PlyFormat> = None;
+    let mut elements: Vec<PlyElement> = vec![];
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens[..] {
+            ["format", name, _] => format = Some(match name {
+                "ascii" => PlyFormat::Ascii,
+                "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                _ => return Err(format!("unknown format '{}'", name))
+            }),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count '{}'", count))?,
+                properties: vec![]
+            }),
+            ["property", "list", count_type, item_type, name] => elements.last_mut()
+                .ok_or_else(|| "property before element".to_string())?
+                .properties.push(PlyProperty {
+                    name: name.to_string(),
+                    scalar: PlyScalar::parse(item_type)?,
+                    list_count: Some(PlyScalar::parse(count_type)?)
+                }),
+            ["property", scalar, name] => elements.last_mut()
+                .ok_or_else(|| "property before element".to_string())?
+                .properties.push(PlyProperty {
+                    name: name.to_string(),
+                    scalar: PlyScalar::parse(scalar)?,
+                    list_count: None
+                }),
+            _ => {}
+        }
+    }
+
+    let mut reader: PlyReader = PlyReader {
+        format: format.ok_or_else(|| "missing format line".to_string())?,
+        body: &content[body_start..],
+        position: 0
+    };
+
+    let mut ply: PlyData = PlyData { positions: vec![], normals: None, tex_coords: None, colors: None, faces: vec![] };
+    for element in &elements {
+        if element.name == "vertex" {
+            let has = |names: &[&str]| element.properties.iter().any(|p: &PlyProperty| names.contains(&p.name.as_str()));
+            // Every vertex takes at least a byte, which keeps a damaged count from allocating past the file
+            let capacity: usize = element.count.min(reader.body.len());
+            ply.positions.reserve(capacity);
+            if has(&["nx"]) { ply.normals = Some(Vec::with_capacity(capacity)) }
+            if has(&["s", "u", "texture_u"]) { ply.tex_coords = Some(Vec::with_capacity(capacity)) }
+            if has(&["red"]) { ply.colors = Some(Vec::with_capacity(capacity)) }
+        }
+
+        for _ in 0..element.count {
+            let mut values: HashMap<&str, f64> = HashMap::new();
+            for property in &element.properties {
+                match property.list_count {
+                    Some(count_scalar) => {
+                        let is_face_list: bool = element.name == "face"
+                            && (property.name == "vertex_indices" || property.name == "vertex_index");
+                        let count: usize = reader.read(count_scalar)? as usize;
+                        let mut list: Vec<usize> = Vec::with_capacity(count.min(reader.body.len()));
+                        for _ in 0..count {
+                            let value: f64 = reader.read(property.scalar)?;
+                            // `as usize` would quietly turn a negative index into vertex 0
+                            if is_face_list && (value < 0.0 || value.is_nan()) {
+                                return Err(format!("face references missing vertex {}", value))
+                            }
+                            list.push(value as usize);
+                        }
+                        if is_face_list && count >= 3 {
+                            ply.faces.push(list);
+                        }
+                    },
+                    None => {
+                        let value: f64 = reader.read(property.scalar)?;
+                        let value: f64 = match property.name.as_str() {
+                            "red" | "green" | "blue" => value / property.scalar.color_scale(),
+                            _ => value
+                        };
+                        values.insert(property.name.as_str(), value);
+                    }
+                }
+            }
+
+            if element.name != "vertex" {
+                continue;
+            }
+            let get = |names: &[&str]| -> f64 {
+                names.iter().find_map(|name: &&str| values.get(name).copied()).unwrap_or(0.0)
+            };
+            ply.positions.push(Vector3::new(get(&["x"]), get(&["y"]), get(&["z"])));
+            if let Some(normals) = ply.normals.as_mut() {
+                normals.push(Vector3::new(get(&["nx"]), get(&["ny"]), get(&["nz"])).normalize());
+            }
+            if let Some(tex_coords) = ply.tex_coords.as_mut() {
+                tex_coords.push(Vector2::new(get(&["s", "u", "texture_u"]), get(&["t", "v", "texture_v"])));
+            }
+            if let Some(colors) = ply.colors.as_mut() {
+                colors.push(Color::new(get(&["red"]), get(&["green"]), get(&["blue"])));
+            }
+        }
+    }
+
+    if let Some(&index) = ply.faces.iter().flatten().find(|&&index: &&usize| index >= ply.positions.len()) {
+        return Err(format!("face references missing vertex {}", index))
+    }
+
+    Ok(ply)
+}
+
+pub fn import_texture(path: &str) -> Result<Vector2D<Color>, RTracerError> {
     println!("Loading texture: {}", path);
-    let image_result: Result<DynamicImage, ImageError> = image::open(&Path::new(path));
-    if let Err(err) = image_result {
-        eprintln!("Failed to open image: {}", err);
-        return Vector2D::new(0, 0, Color::black());
+    let image: DynamicImage = open_image(path)
+        .map_err(|err: ImageError| RTracerError::TextureLoad { path: path.to_string(), source: err })?;
+    // An empty texture would later be sampled with a modulo by zero
+    if image.width() == 0 || image.height() == 0 {
+        return Err(RTracerError::EmptyTexture { path: path.to_string() })
     }
-    
-    let image: DynamicImage = image_result.unwrap();
+
+    Ok(image_to_vector2d(image))
+}
+
+fn open_image(path: &str) -> Result<DynamicImage, ImageError> {
+    if !path.to_lowercase().ends_with(".hdr") {
+        return image::open(Path::new(path))
+    }
+
+    // image::open tone maps Radiance files down to 8 bits, so they are decoded here instead
+    let file: File = File::open(path).map_err(ImageError::IoError)?;
+    let decoder: HdrDecoder<BufReader<File>> = HdrDecoder::new(BufReader::new(file))?;
+    let metadata: HdrMetadata = decoder.metadata();
+    let pixels: Vec<f32> = decoder.read_image_hdr()?.into_iter().flat_map(|pixel: Rgb<f32>| pixel.0).collect();
+    let image: Option<Rgb32FImage> = Rgb32FImage::from_raw(metadata.width, metadata.height, pixels);
+    Ok(image.map_or(DynamicImage::new_rgb32f(0, 0), DynamicImage::ImageRgb32F))
+}
+
+pub fn image_to_vector2d(image: DynamicImage) -> Vector2D<Color> {
+    // EXR and Radiance .hdr files decode to float images, keep their values above 1.0
+    if let DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) = image {
+        return hdr_image_to_vector2d(image.into_rgb32f())
+    }
+
     let rgb_image: RgbImage = image.into_rgb8();
     
     let width: usize = rgb_image.width() as usize;
@@ -161,9 +709,28 @@ pub fn import_texture(path: &str) -> Vector2D<Color> {
     pixel_vector
 }
 
-pub fn save_vector2d_as_png(vector: &Vector2D<Color>, filename: &str) -> Result<(), image::ImageError> {
+fn hdr_image_to_vector2d(rgb_image: Rgb32FImage) -> Vector2D<Color> {
+    let width: usize = rgb_image.width() as usize;
+    let height: usize = rgb_image.height() as usize;
+    let mut pixel_vector: Vector2D<Color> = Vector2D::new(width, height, Color::black());
+
+    for (x, y, pixel) in rgb_image.enumerate_pixels() {
+        // Negative values and NaNs show up in some EXRs and would poison every ray that samples them
+        let channel = |value: f32| -> f64 { if value.is_finite() { f64::max(value as f64, 0.0) } else { 0.0 } };
+        pixel_vector.set(y as usize, x as usize, Color::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2])));
+    }
+
+    pixel_vector
+}
+
+pub fn save_vector2d_as_png(vector: &Vector2D<Color>, filename: &str) -> Result<(), RTracerError> {
+    save_vector2d_as_png_with(vector, filename, PostProcessing::RemoveFireflies)
+}
+
+pub fn save_vector2d_as_png_with(vector: &Vector2D<Color>, filename: &str,
+    postprocessing: PostProcessing) -> Result<(), RTracerError> {
     let mut image = RgbImage::new(vector.width as u32, vector.height as u32);
-    for (i, color) in remove_fireflies(vector).data.iter().enumerate() {
+    for (i, color) in postprocessing.apply(vector).data.iter().enumerate() {
         let x = (i % vector.width) as u32;
         let y = (i / vector.width) as u32;
 
@@ -176,5 +743,267 @@ pub fn save_vector2d_as_png(vector: &Vector2D<Color>, filename: &str) -> Result<
         image.put_pixel(x, y, rgb_color);
     }
 
-    image.save(filename)
-}
\ No newline at end of file
+    image.save(filename).map_err(|err: ImageError| RTracerError::image_write(filename, err))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExrPrecision {
+    Half,
+    Float
+}
+
+// Writes the linear radiance as is, without clamping to [0, 1]
+pub fn save_vector2d_as_exr(vector: &Vector2D<Color>, filename: &str, precision: ExrPrecision,
+    postprocessing: PostProcessing) -> Result<(), RTracerError> {
+    write_exr(&postprocessing.apply(vector), filename, precision, LayerAttributes::default())
+}
+
+// Same as save_vector2d_as_exr, with the metadata stored as header attributes
+pub fn save_vector2d_as_exr_with_metadata(vector: &Vector2D<Color>, filename: &str, precision: ExrPrecision,
+    postprocessing: PostProcessing, metadata: &RenderMetadata) -> Result<(), RTracerError> {
+    write_exr(&postprocessing.apply(vector), filename, precision, metadata.to_exr_attributes())
+}
+
+fn write_exr(frame: &Vector2D<Color>, filename: &str, precision: ExrPrecision,
+    attributes: LayerAttributes) -> Result<(), RTracerError> {
+    let pixel = |x: usize, y: usize| -> (f32, f32, f32) {
+        let color: &Color = frame.get(y, x);
+        (color.red as f32, color.green as f32, color.blue as f32)
+    };
+    let size: (usize, usize) = (frame.width, frame.height);
+
+    let result = match precision {
+        ExrPrecision::Half => {
+            let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+                let (r, g, b) = pixel(x, y);
+                (f16::from_f32(r), f16::from_f32(g), f16::from_f32(b))
+            });
+            Image::from_layer(Layer::new(size, attributes, Encoding::default(), channels)).write().to_file(filename)
+        },
+        ExrPrecision::Float => {
+            let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| pixel(x, y));
+            Image::from_layer(Layer::new(size, attributes, Encoding::default(), channels)).write().to_file(filename)
+        }
+    };
+    result.map_err(|err: exr::error::Error| RTracerError::image_write(filename, err))
+}
+
+// Portable float map: little endian RGB floats, stored bottom row first
+pub fn save_vector2d_as_pfm(vector: &Vector2D<Color>, filename: &str,
+    postprocessing: PostProcessing) -> Result<(), RTracerError> {
+    let frame: Vector2D<Color> = postprocessing.apply(vector);
+    let file: File = File::create(filename).map_err(|err| RTracerError::io(filename, err))?;
+    let mut writer: BufWriter<File> = BufWriter::new(file);
+
+    let mut bytes: Vec<u8> = format!("PF\n{} {}\n-1.0\n", frame.width, frame.height).into_bytes();
+    bytes.reserve(12 * frame.width * frame.height);
+    for y in (0..frame.height).rev() {
+        for x in 0..frame.width {
+            let color: &Color = frame.get(y, x);
+            for value in [color.red, color.green, color.blue] {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+    }
+
+    writer.write_all(&bytes)
+        .and_then(|_| writer.flush())
+        .map_err(|err| RTracerError::io(filename, err))
+}
+
+// 16 bits per channel PNG, still clamped to [0, 1] but without the banding of the 8 bit output
+pub fn save_vector2d_as_png16(vector: &Vector2D<Color>, filename: &str,
+    postprocessing: PostProcessing) -> Result<(), RTracerError> {
+    let frame: Vector2D<Color> = postprocessing.apply(vector);
+    let image: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::from_fn(frame.width as u32, frame.height as u32, |x: u32, y: u32| {
+        let color: &Color = frame.get(y as usize, x as usize);
+        let channel = |value: f64| -> u16 { (value.clamp(0.0, 1.0) * 65535.0).round() as u16 };
+        Rgb([channel(color.red), channel(color.green), channel(color.blue)])
+    });
+
+    image.save(filename).map_err(|err: ImageError| RTracerError::image_write(filename, err))
+}
+
+// Same as save_vector2d_as_png_with, with the metadata stored as tEXt chunks
+pub fn save_vector2d_as_png_with_metadata(vector: &Vector2D<Color>, filename: &str,
+    postprocessing: PostProcessing, metadata: &RenderMetadata) -> Result<(), RTracerError> {
+    let frame: Vector2D<Color> = postprocessing.apply(vector);
+    let bytes: Vec<u8> = frame.data.iter()
+        .flat_map(|color: &Color| [color.red, color.green, color.blue])
+        .map(|value: f64| (value * 255.0) as u8)
+        .collect();
+    write_png(&frame, filename, png::BitDepth::Eight, &bytes, metadata)
+}
+
+// Same as save_vector2d_as_png16, with the metadata stored as tEXt chunks
+pub fn save_vector2d_as_png16_with_metadata(vector: &Vector2D<Color>, filename: &str,
+    postprocessing: PostProcessing, metadata: &RenderMetadata) -> Result<(), RTracerError> {
+    let frame: Vector2D<Color> = postprocessing.apply(vector);
+    let bytes: Vec<u8> = frame.data.iter()
+        .flat_map(|color: &Color| [color.red, color.green, color.blue])
+        .flat_map(|value: f64| ((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes())
+        .collect();
+    write_png(&frame, filename, png::BitDepth::Sixteen, &bytes, metadata)
+}
+
+fn write_png(frame: &Vector2D<Color>, filename: &str, depth: png::BitDepth, bytes: &[u8],
+    metadata: &RenderMetadata) -> Result<(), RTracerError> {
+    let file: File = File::create(filename).map_err(|err| RTracerError::io(filename, err))?;
+    let mut encoder: png::Encoder<BufWriter<File>> = png::Encoder::new(BufWriter::new(file), frame.width as u32, frame.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(depth);
+
+    let mut chunks: Vec<(String, String)> = vec![(String::from("Software"), format!("RTracer {}", metadata.crate_version))];
+    chunks.extend(metadata.to_pairs());
+    let result: Result<(), png::EncodingError> = chunks.into_iter()
+        .try_for_each(|(keyword, text)| encoder.add_text_chunk(keyword, text))
+        .and_then(|_| encoder.write_header())
+        .and_then(|mut writer: png::Writer<BufWriter<File>>| writer.write_image_data(bytes));
+    result.map_err(|err: png::EncodingError| RTracerError::image_write(filename, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, extension: &str, content: &[u8]) -> String {
+        let path: PathBuf = std::env::temp_dir().join(format!("r_tracer_{}_{}.{}", name, std::process::id(), extension));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn load(name: &str, extension: &str, content: &[u8], options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+        let path: String = write_temp(name, extension, content);
+        let tris: Result<Vec<Tri>, RTracerError> = load_model(&path, Material::empty(), options);
+        fs::remove_file(&path).unwrap();
+        tris
+    }
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        a.distance(b) < 1e-6
+    }
+
+    // One triangle facing up in a Blender scene, (0, 0, 0), (1, 0, 0), (0, 1, 0) with normal +Z, exported in
+    // each format's own convention together with its normals
+    fn blender_triangle() -> [(&'static str, Vec<u8>); 3] {
+        let obj: &str = "v 0 0 0\nv 1 0 0\nv 0 0 -1\nvn 0 1 0\nf 1//1 2//1 3//1\n";
+        // Binary STL, an 80 byte header, the triangle count, then the normal and vertices of each triangle
+        let mut stl: Vec<u8> = vec![0; 80];
+        stl.extend(1u32.to_le_bytes());
+        for value in [0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            stl.extend(value.to_le_bytes());
+        }
+        stl.extend(0u16.to_le_bytes());
+        let ply: &str = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            property float nx\nproperty float ny\nproperty float nz\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0 0 0 1\n1 0 0 0 0 1\n0 1 0 0 0 1\n3 0 1 2\n";
+        [("obj", obj.as_bytes().to_vec()), ("stl", stl), ("ply", ply.as_bytes().to_vec())]
+    }
+
+    #[test]
+    fn formats_line_up_with_their_own_conventions() {
+        for (extension, content) in blender_triangle() {
+            let tris: Vec<Tri> = load("convention", extension, &content, &ImportOptions::for_file(&format!("a.{}", extension))).unwrap();
+            assert_eq!(tris.len(), 1, "{}", extension);
+            let tri: Tri = tris[0];
+            assert!(close(tri.p1, Vector3::zero()) && close(tri.p2, Vector3::new(1.0, 0.0, 0.0))
+                && close(tri.p3, Vector3::new(0.0, -1.0, 0.0)), "{}", extension);
+            for normal in [tri.normal, tri.p1_normal, tri.p2_normal, tri.p3_normal] {
+                assert!(close(normal, Vector3::new(0.0, 0.0, 1.0)), "{}", extension);
+            }
+        }
+    }
+
+    #[test]
+    fn flipped_winding_turns_every_normal_around() {
+        for (extension, content) in blender_triangle() {
+            let options: ImportOptions = ImportOptions::for_file(&format!("a.{}", extension)).with_flip_winding(true);
+            let tri: Tri = load("flipped", extension, &content, &options).unwrap()[0];
+            for normal in [tri.normal, tri.p1_normal, tri.p2_normal, tri.p3_normal] {
+                assert!(close(normal, Vector3::new(0.0, 0.0, -1.0)), "{}", extension);
+            }
+        }
+    }
+    // A unit quad with per vertex colors, as one four sided face
+    const PLY_QUAD: [(f32, f32, f32, u8); 4] = [(0.0, 0.0, 0.0, 255), (1.0, 0.0, 0.0, 0), (1.0, 1.0, 0.5, 51), (0.0, 1.0, 0.5, 102)];
+
+    fn ply_header(format: &str) -> String {
+        format!("ply\nformat {} 1.0\ncomment made by hand\nelement vertex 4\nproperty float x\nproperty float y\n\
+            property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\n\
+            property list uchar int vertex_indices\nend_header\n", format)
+    }
+
+    fn ascii_ply() -> Vec<u8> {
+        let mut ply: String = ply_header("ascii");
+        for (x, y, z, shade) in PLY_QUAD {
+            ply += &format!("{} {} {} {} {} {}\n", x, y, z, shade, shade, shade);
+        }
+        ply += "4 0 1 2 3\n";
+        ply.into_bytes()
+    }
+
+    fn binary_ply(big_endian: bool) -> Vec<u8> {
+        let mut ply: Vec<u8> = ply_header(if big_endian { "binary_big_endian" } else { "binary_little_endian" }).into_bytes();
+        let float = |value: f32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        for (x, y, z, shade) in PLY_QUAD {
+            ply.extend([float(x), float(y), float(z)].concat());
+            ply.extend([shade; 3]);
+        }
+        ply.push(4);
+        for index in 0..4i32 {
+            ply.extend(if big_endian { index.to_be_bytes() } else { index.to_le_bytes() });
+        }
+        ply
+    }
+
+    #[test]
+    fn reads_every_ply_encoding() {
+        for (name, content) in [("ascii", ascii_ply()), ("little endian", binary_ply(false)), ("big endian", binary_ply(true))] {
+            let ply: PlyData = parse_ply(&content).unwrap_or_else(|err: String| panic!("{}: {}", name, err));
+            assert_eq!(ply.faces, vec![vec![0, 1, 2, 3]], "{}", name);
+            assert!(ply.normals.is_none() && ply.tex_coords.is_none(), "{}", name);
+            let colors: Vec<Color> = ply.colors.unwrap();
+            assert_eq!((ply.positions.len(), colors.len()), (4, 4), "{}", name);
+            for ((x, y, z, shade), (position, color)) in PLY_QUAD.into_iter().zip(ply.positions.iter().zip(colors)) {
+                let shade: f64 = shade as f64 / 255.0;
+                assert!(close(*position, Vector3::new(x as f64, y as f64, z as f64)), "{}", name);
+                assert!(color == Color::new(shade, shade, shade), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_plys_are_errors() {
+        for content in [ascii_ply(), binary_ply(false), binary_ply(true)] {
+            for cut in [2, 5, 30] {
+                assert_eq!(parse_ply(&content[..content.len() - cut]).err().unwrap(), "unexpected end of file");
+            }
+        }
+        let no_body: Vec<u8> = ply_header("binary_little_endian").into_bytes();
+        assert_eq!(parse_ply(&no_body).err().unwrap(), "unexpected end of file");
+        assert_eq!(parse_ply(b"ply\nformat ascii 1.0\n").err().unwrap(), "missing end_header");
+    }
+
+    #[test]
+    fn damaged_counts_do_not_allocate_past_the_file() {
+        let ply: String = String::from_utf8(ascii_ply()).unwrap();
+        let huge_vertex_count: String = ply.replace("element vertex 4", "element vertex 999999999999999");
+        assert_eq!(parse_ply(huge_vertex_count.as_bytes()).err().unwrap(), "unexpected end of file");
+        let huge_list: String = ply.replace("4 0 1 2 3", "4294967295 0 1 2 3");
+        assert_eq!(parse_ply(huge_list.as_bytes()).err().unwrap(), "unexpected end of file");
+    }
+
+    #[test]
+    fn out_of_range_face_indices_are_errors() {
+        let ply: String = String::from_utf8(ascii_ply()).unwrap();
+        let past_the_end: String = ply.replace("4 0 1 2 3", "4 0 1 2 4");
+        assert_eq!(parse_ply(past_the_end.as_bytes()).err().unwrap(), "face references missing vertex 4");
+        let negative: String = ply.replace("4 0 1 2 3", "4 0 -1 2 3");
+        assert_eq!(parse_ply(negative.as_bytes()).err().unwrap(), "face references missing vertex -1");
+
+        let path: String = write_temp("ply_range", "ply", past_the_end.as_bytes());
+        let result: Result<Vec<Tri>, RTracerError> = load_model(&path, Material::empty(), &ImportOptions::for_file(&path));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RTracerError::ModelParse { .. })));
+    }
+}
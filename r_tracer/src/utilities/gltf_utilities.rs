@@ -0,0 +1,551 @@
+use gltf::{Gltf, Document, Node, Primitive};
+use gltf::mesh::{Mode, Semantic};
+use gltf::material::AlphaMode;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::DynamicImage;
+use crate::spacial::tri::Tri;
+use crate::spacial::mesh_object::MeshObject;
+use crate::datatypes::material::Material;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::utilities::file_utilities::image_to_vector2d;
+use crate::error::RTracerError;
+use crate::utilities::import_options::ImportOptions;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Matrix4 = [[f64; 4]; 4];
+
+
+pub fn import_gltf(file_path: &str, texture_maps: &mut Vec<Vector2D<Color>>, options: &ImportOptions)
+    -> Result<(Vec<MeshObject>, Vec<Material>), RTracerError> {
+    let gltf: Gltf = Gltf::open(file_path).map_err(|err: gltf::Error| match err {
+        gltf::Error::Io(err) => RTracerError::io(file_path, err),
+        err => RTracerError::model_parse(file_path, err)
+    })?;
+    let base_dir: &Path = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let buffers: Vec<Vec<u8>> = load_buffers(&gltf, file_path, base_dir)?;
+
+    let mut textures: GltfTextures = GltfTextures::new(&gltf.document, &buffers, file_path, base_dir);
+    let materials: Vec<Material> = gltf.document.materials()
+        .map(|material: gltf::Material| convert_material(&material, &mut textures, texture_maps))
+        .collect::<Result<Vec<Material>, RTracerError>>()?;
+    let default_material: Material = default_gltf_material();
+
+    let mut meshes: Vec<MeshObject> = vec![];
+    let roots: Vec<Node> = match gltf.document.default_scene().or_else(|| gltf.document.scenes().next()) {
+        Some(scene) => scene.nodes().collect(),
+        None => gltf.document.nodes().collect()
+    };
+    for node in roots {
+        meshes.extend(collect_node_meshes(&node, IDENTITY, &buffers, &materials, default_material, options, file_path)?);
+    }
+
+    Ok((meshes, materials))
+}
+
+fn collect_node_meshes(node: &Node, parent_transform: Matrix4, buffers: &[Vec<u8>], materials: &[Material],
+    default_material: Material, options: &ImportOptions, file_path: &str) -> Result<Vec<MeshObject>, RTracerError> {
+    let mut meshes: Vec<MeshObject> = vec![];
+    let local: [[f32; 4]; 4] = node.transform().matrix();
+    let transform: Matrix4 = multiply(&parent_transform, &to_f64_matrix(&local));
+
+    if let Some(mesh) = node.mesh() {
+        let mut tris: Vec<Tri> = vec![];
+        let mut smooth_shading: bool = true;
+        for primitive in mesh.primitives() {
+            let material: Material = primitive.material().index()
+                .map_or(default_material, |index: usize| materials[index]);
+            smooth_shading &= primitive.get(&Semantic::Normals).is_some();
+            tris.extend(read_primitive(&primitive, &transform, buffers, material, options, file_path)?);
+        }
+        let name: &str = node.name().or(mesh.name()).unwrap_or("");
+        meshes.push(MeshObject::new(tris, smooth_shading).with_name(name));
+    }
+
+    for child in node.children() {
+        meshes.extend(collect_node_meshes(&child, transform, buffers, materials, default_material, options, file_path)?);
+    }
+    Ok(meshes)
+}
+
+fn read_primitive(primitive: &Primitive, transform: &Matrix4, buffers: &[Vec<u8>], material: Material,
+    options: &ImportOptions, file_path: &str) -> Result<Vec<Tri>, RTracerError> {
+    if primitive.mode() != Mode::Triangles {
+        println!("Skipping glTF primitive with unsupported mode {:?}", primitive.mode());
+        return Ok(vec![])
+    }
+
+    let reader = primitive.reader(|buffer: gltf::Buffer| buffers.get(buffer.index()).map(|data: &Vec<u8>| data.as_slice()));
+    let positions: Vec<Vector3> = match reader.read_positions() {
+        Some(positions) => positions.map(|p: [f32; 3]| to_renderer_point(options, transform_point(transform, p))).collect(),
+        None => return Ok(vec![])
+    };
+
+    let normal_matrix: Matrix4 = normal_matrix(transform);
+    let normals: Option<Vec<Vector3>> = reader.read_normals().map(|normals| normals
+        .map(|n: [f32; 3]| to_renderer_normal(options, transform_direction(&normal_matrix, n)).normalize())
+        .collect());
+    let tex_coords: Option<Vec<Vector2>> = reader.read_tex_coords(0).map(|tex_coords| tex_coords
+        .into_f32()
+        // glTF puts the texture origin in the top left corner, OBJ in the bottom left
+        .map(|t: [f32; 2]| options.convert_uv(t[0] as f64, 1.0 - t[1] as f64))
+        .collect());
+    // The spec has tangents ignored on primitives without normals
+    let tangents: Option<Vec<(Vector3, Vector3)>> = reader.read_tangents().zip(reader.read_normals()).map(|(tangents, normals)| tangents
+        .zip(normals)
+        .map(|(t, n): ([f32; 4], [f32; 3])| {
+            let normal: [f64; 3] = [n[0] as f64, n[1] as f64, n[2] as f64];
+            let tangent: [f64; 3] = [t[0] as f64, t[1] as f64, t[2] as f64];
+            let bitangent: [f64; 3] = scale(cross(normal, tangent), t[3] as f64);
+            (
+                to_renderer_direction(options, transform_direction(transform, [t[0], t[1], t[2]])).normalize(),
+                to_renderer_direction(options, transform_direction(transform, to_f32(bitangent))).normalize()
+            )
+        })
+        .collect());
+    let indices: Vec<usize> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().map(|i: u32| i as usize).collect(),
+        None => (0..positions.len()).collect()
+    };
+
+    let mut attribute_lengths: Vec<(&str, usize)> = vec![("POSITION", positions.len())];
+    if let Some(normals) = &normals {
+        attribute_lengths.push(("NORMAL", normals.len()));
+    }
+    if let Some(tex_coords) = &tex_coords {
+        attribute_lengths.push(("TEXCOORD_0", tex_coords.len()));
+    }
+    if let Some(tangents) = &tangents {
+        attribute_lengths.push(("TANGENT", tangents.len()));
+    }
+    for &index in &indices {
+        if let Some((name, len)) = attribute_lengths.iter().find(|&&(_, len): &&(&str, usize)| index >= len) {
+            return Err(RTracerError::model_parse(file_path,
+                format!("vertex index {} is out of range of the {} attribute with {} entries", index, name, len)))
+        }
+    }
+
+    // Mirroring node transforms flip the winding order, on top of whatever the import options ask for
+    let flip_winding: bool = (determinant3(transform) < 0.0) != options.reverses_winding();
+
+    let mut tris: Vec<Tri> = vec![];
+    for chunk in indices.chunks(3) {
+        if chunk.len() != 3 {
+            continue;
+        }
+        let (i1, i2, i3) = if flip_winding { (chunk[0], chunk[2], chunk[1]) } else { (chunk[0], chunk[1], chunk[2]) };
+        let (p1, p2, p3) = (positions[i1], positions[i2], positions[i3]);
+        let face_normal: Vector3 = Tri::compute_face_normal(p1, p2, p3);
+        let (n1, n2, n3) = match &normals {
+            Some(normals) => (normals[i1], normals[i2], normals[i3]),
+            None => (face_normal, face_normal, face_normal)
+        };
+        let (t1, t2, t3) = match &tex_coords {
+            Some(tex_coords) => (tex_coords[i1], tex_coords[i2], tex_coords[i3]),
+            None => (Vector2::zero(), Vector2::zero(), Vector2::zero())
+        };
+
+        let mut tri: Tri = Tri::new(p1, p2, p3, n1, n2, n3, face_normal, t1, t2, t3, material);
+        if let Some(tangents) = &tangents {
+            let (tangent, bitangent) = tangents[i1];
+            let bitangent_sign: f64 = if n1.cross(&tangent) * bitangent < 0.0 { -1.0 } else { 1.0 };
+            tri = tri.with_tangents(tangents[i1].0, tangents[i2].0, tangents[i3].0, bitangent_sign);
+        }
+        tris.push(tri);
+    }
+
+    Ok(tris)
+}
+
+fn convert_material(material: &gltf::Material, textures: &mut GltfTextures,
+    texture_maps: &mut Vec<Vector2D<Color>>) -> Result<Material, RTracerError> {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color: [f32; 4] = pbr.base_color_factor();
+    let diffuse_color: Color = Color::new(base_color[0] as f64, base_color[1] as f64, base_color[2] as f64);
+    let metallic: f64 = pbr.metallic_factor() as f64;
+    let roughness: f64 = pbr.roughness_factor() as f64;
+    let emissive: [f32; 3] = material.emissive_factor();
+    let emission_color: Color = Color::new(emissive[0] as f64, emissive[1] as f64, emissive[2] as f64);
+
+    let diffuse_map: Option<usize> = pbr.base_color_texture()
+        .map(|info| textures.color_map(info.texture().source().index(), texture_maps))
+        .transpose()?;
+    let emission_map: Option<usize> = material.emissive_texture()
+        .map(|info| textures.color_map(info.texture().source().index(), texture_maps))
+        .transpose()?;
+    let normal_texture = material.normal_texture();
+    let normal_map: Option<usize> = normal_texture.as_ref()
+        .map(|normal| textures.color_map(normal.texture().source().index(), texture_maps))
+        .transpose()?;
+
+    // Roughness lives in the green channel and metalness in the blue channel of the same image
+    let (smoothness_map, specular_map) = match pbr.metallic_roughness_texture() {
+        Some(info) => {
+            let image_index: usize = info.texture().source().index();
+            (
+                Some(textures.channel_map(image_index, 1, roughness, true, texture_maps)?),
+                Some(textures.channel_map(image_index, 2, metallic, false, texture_maps)?)
+            )
+        },
+        None => (None, None)
+    };
+
+    let dielectric: f64 = match material.alpha_mode() {
+        AlphaMode::Blend => 1.0 - base_color[3] as f64,
+        _ => 0.0
+    };
+
+    Ok(Material::new(
+        diffuse_color, emission_color,
+        Color::lerp(Color::white(), diffuse_color, metallic), Color::white(),
+        if emission_color != Color::black() { 1.0 } else { 0.0 },
+        1.0 - roughness, metallic, dielectric,
+        material.ior().map_or(1.5, |ior: f32| ior as f64),
+        normal_texture.map_or(1.0, |normal| normal.scale() as f64), true,
+        diffuse_map, emission_map, None, None,
+        normal_map, smoothness_map, specular_map
+    ))
+}
+
+// Primitives without a material use the spec's default: white, fully metallic and fully rough
+fn default_gltf_material() -> Material {
+    Material::new(
+        Color::white(), Color::black(), Color::white(), Color::white(),
+        0.0, 0.0, 1.0, 0.0, 1.5, 1.0, true,
+        None, None, None, None, None, None, None
+    )
+}
+
+// Decodes glTF images on first use and remembers where they were placed in the scene's texture list
+struct GltfTextures<'a> {
+    document: &'a Document,
+    buffers: &'a [Vec<u8>],
+    file_path: &'a str,
+    base_dir: &'a Path,
+    images: HashMap<usize, DynamicImage>,
+    color_maps: HashMap<usize, usize>,
+    channel_maps: HashMap<(usize, usize), usize>
+}
+
+impl<'a> GltfTextures<'a> {
+    fn new(document: &'a Document, buffers: &'a [Vec<u8>], file_path: &'a str, base_dir: &'a Path) -> GltfTextures<'a> {
+        GltfTextures {
+            document,
+            buffers,
+            file_path,
+            base_dir,
+            images: HashMap::new(),
+            color_maps: HashMap::new(),
+            channel_maps: HashMap::new()
+        }
+    }
+
+    fn image(&mut self, image_index: usize) -> Result<&DynamicImage, RTracerError> {
+        if !self.images.contains_key(&image_index) {
+            let image: DynamicImage = match self.document.images().nth(image_index) {
+                Some(image) => self.decode_image(&image)?,
+                None => return Err(RTracerError::model_parse(self.file_path, format!("missing image {}", image_index)))
+            };
+            self.images.insert(image_index, image);
+        }
+        Ok(&self.images[&image_index])
+    }
+
+    fn decode_image(&self, image: &gltf::Image) -> Result<DynamicImage, RTracerError> {
+        let (bytes, path): (Vec<u8>, String) = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let bytes: Option<&[u8]> = self.buffers.get(view.buffer().index())
+                    .and_then(|buffer: &Vec<u8>| buffer.get(view.offset()..view.offset() + view.length()));
+                match bytes {
+                    Some(bytes) => (bytes.to_vec(), format!("{}#image{}", self.file_path, image.index())),
+                    None => return Err(RTracerError::model_parse(self.file_path,
+                        format!("image {} lies outside its buffer", image.index())))
+                }
+            },
+            gltf::image::Source::Uri { uri, .. } => {
+                (read_uri(uri, self.file_path, self.base_dir)?, uri_path(uri, self.base_dir).to_string_lossy().into_owned())
+            }
+        };
+        let decoded: DynamicImage = image::load_from_memory(&bytes)
+            .map_err(|err: image::ImageError| RTracerError::TextureLoad { path: path.clone(), source: err })?;
+        if decoded.width() == 0 || decoded.height() == 0 {
+            return Err(RTracerError::EmptyTexture { path })
+        }
+        Ok(decoded)
+    }
+
+    fn color_map(&mut self, image_index: usize, texture_maps: &mut Vec<Vector2D<Color>>) -> Result<usize, RTracerError> {
+        if let Some(&index) = self.color_maps.get(&image_index) {
+            return Ok(index)
+        }
+        let map: Vector2D<Color> = image_to_vector2d(self.image(image_index)?.clone());
+        texture_maps.push(map);
+        self.color_maps.insert(image_index, texture_maps.len() - 1);
+        Ok(texture_maps.len() - 1)
+    }
+
+    // Splits a single channel out into a greyscale map scaled by `factor`, optionally inverted
+    // (roughness is stored, smoothness is what the renderer samples)
+    fn channel_map(&mut self, image_index: usize, channel: usize, factor: f64, invert: bool,
+        texture_maps: &mut Vec<Vector2D<Color>>) -> Result<usize, RTracerError> {
+        if let Some(&index) = self.channel_maps.get(&(image_index, channel)) {
+            return Ok(index)
+        }
+        let mut map: Vector2D<Color> = image_to_vector2d(self.image(image_index)?.clone());
+        for color in map.data.iter_mut() {
+            let value: f64 = [color.red, color.green, color.blue][channel] * factor;
+            let value: f64 = if invert { 1.0 - value } else { value };
+            *color = Color::new(value, value, value);
+        }
+        texture_maps.push(map);
+        self.channel_maps.insert((image_index, channel), texture_maps.len() - 1);
+        Ok(texture_maps.len() - 1)
+    }
+}
+
+fn load_buffers(gltf: &Gltf, file_path: &str, base_dir: &Path) -> Result<Vec<Vec<u8>>, RTracerError> {
+    gltf.document.buffers().map(|buffer: gltf::Buffer| {
+        let mut data: Vec<u8> = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf.blob.clone()
+                .ok_or_else(|| RTracerError::model_parse(file_path, "missing binary chunk"))?,
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, file_path, base_dir)?
+        };
+        if data.len() < buffer.length() {
+            return Err(RTracerError::model_parse(file_path, format!("buffer {} is truncated", buffer.index())))
+        }
+        // Buffers are padded to a multiple of four bytes in .glb files
+        data.truncate(buffer.length());
+        Ok(data)
+    }).collect()
+}
+
+fn read_uri(uri: &str, file_path: &str, base_dir: &Path) -> Result<Vec<u8>, RTracerError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data.split_once(";base64,")
+            .ok_or_else(|| RTracerError::model_parse(file_path, "data URI is not base64 encoded"))?;
+        return BASE64.decode(encoded).map_err(|err| RTracerError::model_parse(file_path, err))
+    }
+    let path: PathBuf = uri_path(uri, base_dir);
+    fs::read(&path).map_err(|err| RTracerError::io(&path.to_string_lossy(), err))
+}
+
+fn uri_path(uri: &str, base_dir: &Path) -> PathBuf {
+    base_dir.join(uri.replace("%20", " "))
+}
+
+// Buffers and images the file keeps outside of itself, for the mesh cache key
+pub(crate) fn gltf_external_files(file_path: &str) -> Vec<PathBuf> {
+    let gltf: Gltf = match Gltf::open(file_path) {
+        Ok(gltf) => gltf,
+        Err(_) => return vec![]
+    };
+    let base_dir: &Path = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let buffer_uris = gltf.document.buffers().filter_map(|buffer: gltf::Buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => Some(uri),
+        gltf::buffer::Source::Bin => None
+    });
+    let image_uris = gltf.document.images().filter_map(|image: gltf::Image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None
+    });
+    buffer_uris.chain(image_uris)
+        .filter(|uri: &&str| !uri.starts_with("data:"))
+        .map(|uri: &str| uri_path(uri, base_dir))
+        .collect()
+}
+
+// glTF is Y up and right handed, which `ImportOptions::y_up` converts the same way as OBJ
+fn to_renderer_point(options: &ImportOptions, v: [f64; 3]) -> Vector3 {
+    options.convert_point(v[0], v[1], v[2])
+}
+
+fn to_renderer_direction(options: &ImportOptions, v: [f64; 3]) -> Vector3 {
+    options.convert_direction(v[0], v[1], v[2])
+}
+
+fn to_renderer_normal(options: &ImportOptions, v: [f64; 3]) -> Vector3 {
+    options.convert_normal(v[0], v[1], v[2])
+}
+
+const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0]
+];
+
+fn to_f64_matrix(m: &[[f32; 4]; 4]) -> Matrix4 {
+    m.map(|column: [f32; 4]| column.map(|value: f32| value as f64))
+}
+
+fn to_f32(v: [f64; 3]) -> [f32; 3] {
+    [v[0] as f32, v[1] as f32, v[2] as f32]
+}
+
+// Matrices are column major, as in the glTF spec
+fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    std::array::from_fn(|c: usize| std::array::from_fn(|r: usize| {
+        (0..4).map(|k: usize| a[k][r] * b[c][k]).sum()
+    }))
+}
+
+fn transform_point(m: &Matrix4, p: [f32; 3]) -> [f64; 3] {
+    let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+    [
+        m[0][0]*x + m[1][0]*y + m[2][0]*z + m[3][0],
+        m[0][1]*x + m[1][1]*y + m[2][1]*z + m[3][1],
+        m[0][2]*x + m[1][2]*y + m[2][2]*z + m[3][2]
+    ]
+}
+
+fn transform_direction(m: &Matrix4, d: [f32; 3]) -> [f64; 3] {
+    let (x, y, z) = (d[0] as f64, d[1] as f64, d[2] as f64);
+    [
+        m[0][0]*x + m[1][0]*y + m[2][0]*z,
+        m[0][1]*x + m[1][1]*y + m[2][1]*z,
+        m[0][2]*x + m[1][2]*y + m[2][2]*z
+    ]
+}
+
+fn determinant3(m: &Matrix4) -> f64 {
+    m[0][0] * (m[1][1]*m[2][2] - m[2][1]*m[1][2])
+        - m[1][0] * (m[0][1]*m[2][2] - m[2][1]*m[0][2])
+        + m[2][0] * (m[0][1]*m[1][2] - m[1][1]*m[0][2])
+}
+
+// Inverse transpose of the upper 3x3, so normals stay perpendicular under non-uniform scaling
+fn normal_matrix(m: &Matrix4) -> Matrix4 {
+    let det: f64 = determinant3(m);
+    if det.abs() < 1e-12 {
+        return *m
+    }
+    std::array::from_fn(|c: usize| std::array::from_fn(|r: usize| {
+        if c == 3 || r == 3 {
+            return IDENTITY[c][r]
+        }
+        let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        (m[c1][r1]*m[c2][r2] - m[c2][r1]*m[c1][r2]) / det
+    }))
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
+fn scale(v: [f64; 3], s: f64) -> [f64; 3] {
+    [v[0]*s, v[1]*s, v[2]*s]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_path() -> String {
+        format!("{}/../RTracerExampleProject/Models/gltf/textured_quad.gltf", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    // Single triangle primitive over an embedded buffer of float positions, optional float VEC4 tangents
+    // and u16 indices
+    fn write_triangle(name: &str, indices: [u16; 3], with_tangents: bool) -> String {
+        let mut bytes: Vec<u8> = vec![];
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for _ in 0..3 {
+            for value in [1.0f32, 0.0, 0.0, 1.0] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        for index in indices {
+            bytes.extend(index.to_le_bytes());
+        }
+        let tangent_attribute: &str = if with_tangents { r#", "TANGENT": 1"# } else { "" };
+        let source: String = format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0{} }}, "indices": 2 }}] }}],
+            "buffers": [{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 48 }},
+                {{ "buffer": 0, "byteOffset": 84, "byteLength": 6 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 0, 1] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC4" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ]
+        }}"#, tangent_attribute, bytes.len(), BASE64.encode(&bytes));
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!("r_tracer_{}_{}.gltf", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn imports_textured_quad_fixture() {
+        let path: String = fixture_path();
+        let mut texture_maps: Vec<Vector2D<Color>> = vec![];
+        let (meshes, materials) = import_gltf(&path, &mut texture_maps, &ImportOptions::for_file(&path)).unwrap();
+
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].name, "quad");
+        assert_eq!(meshes[0].tris.len(), 2);
+
+        // Base color and normal map share one image, loaded once
+        assert_eq!(texture_maps.len(), 1);
+        assert_eq!((texture_maps[0].width, texture_maps[0].height), (1000, 1000));
+        assert_eq!(materials.len(), 1);
+        let material: Material = materials[0];
+        assert_eq!(material.diffuse_color_map_index, Some(0));
+        assert_eq!(material.normal_map_index, Some(0));
+        assert_eq!(material.smoothness_map_index, None);
+        assert_eq!(material.normal_strength, 0.5);
+        assert_eq!(material.smoothness, 0.5);
+        assert_eq!(material.specular, 0.0);
+        assert!(meshes[0].tris.iter().all(|tri: &Tri| tri.material == material));
+
+        // Indices 0, 2, 1 with glTF UVs (0, 1), (1, 0), (1, 1), flipped to a bottom left origin
+        let tri: &Tri = &meshes[0].tris[0];
+        let uvs: Vec<(f64, f64)> = [tri.p1_texture, tri.p2_texture, tri.p3_texture].iter()
+            .map(|uv: &Vector2| (uv.x, uv.y)).collect();
+        assert_eq!(uvs, vec![(0.0, 0.0), (1.0, 1.0), (1.0, 0.0)]);
+
+        for tri in &meshes[0].tris {
+            assert!(tri.has_tangents());
+            assert!((tri.normal.z - 1.0).abs() < 1e-6);
+            for tangent in [tri.p1_tangent, tri.p2_tangent, tri.p3_tangent] {
+                assert!((tangent.magnitude() - 1.0).abs() < 1e-6);
+                assert!((tangent * tri.normal).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_tangents_without_normals() {
+        let path: String = write_triangle("tangents_without_normals", [0, 1, 2], true);
+        let result: Result<(Vec<MeshObject>, Vec<Material>), RTracerError> = import_gltf(&path, &mut vec![], &ImportOptions::for_file(&path));
+        fs::remove_file(&path).unwrap();
+        let (meshes, _) = result.unwrap();
+        assert_eq!(meshes[0].tris.len(), 1);
+        assert!(!meshes[0].tris[0].has_tangents());
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let path: String = write_triangle("out_of_range_index", [0, 1, 5], false);
+        let result: Result<(Vec<MeshObject>, Vec<Material>), RTracerError> = import_gltf(&path, &mut vec![], &ImportOptions::for_file(&path));
+        fs::remove_file(&path).unwrap();
+        match result {
+            Err(RTracerError::ModelParse { message, .. }) => assert!(message.contains("out of range of the POSITION attribute")),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("out of range index was accepted")
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use serde::Deserialize;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::spacial::tri::Tri;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    Y,
+    Z
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Handedness {
+    Right,
+    Left
+}
+
+// Describes the coordinate system an asset was authored in. Loaders convert everything into the
+// renderer's space, which is Z up and left handed (the camera looks down +X with +Y to the right).
+#[derive(Copy, Clone, Debug)]
+pub struct ImportOptions {
+    pub up_axis: UpAxis,
+    pub handedness: Handedness,
+    pub scale: f64,
+    pub flip_winding: bool,
+    pub flip_v: bool
+}
+
+impl ImportOptions {
+    pub fn new(up_axis: UpAxis, handedness: Handedness, scale: f64, flip_winding: bool, flip_v: bool) -> ImportOptions {
+        ImportOptions { up_axis, handedness, scale, flip_winding, flip_v }
+    }
+
+    // OBJ and glTF
+    pub fn y_up() -> ImportOptions {
+        ImportOptions::new(UpAxis::Y, Handedness::Right, 1.0, false, false)
+    }
+
+    // STL and PLY as written by Blender and most CAD tools
+    pub fn z_up() -> ImportOptions {
+        ImportOptions::new(UpAxis::Z, Handedness::Right, 1.0, false, false)
+    }
+
+    // The usual convention of the format, picked by file extension. There is no Default, the right one
+    // depends on the file.
+    pub fn for_file(file_path: &str) -> ImportOptions {
+        if file_path.ends_with(".stl") || file_path.ends_with(".ply") {
+            ImportOptions::z_up()
+        } else {
+            ImportOptions::y_up()
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> ImportOptions {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_flip_winding(mut self, flip_winding: bool) -> ImportOptions {
+        self.flip_winding = flip_winding;
+        self
+    }
+
+    pub fn with_flip_v(mut self, flip_v: bool) -> ImportOptions {
+        self.flip_v = flip_v;
+        self
+    }
+
+    pub fn convert_point(&self, x: f64, y: f64, z: f64) -> Vector3 {
+        self.scale * self.swap_axes(x, y, z)
+    }
+
+    // Directions keep their length, but follow a negative scale
+    pub fn convert_direction(&self, x: f64, y: f64, z: f64) -> Vector3 {
+        self.scale.signum() * self.swap_axes(x, y, z)
+    }
+
+    // Normals stored in the file turn around with the front faces when the winding is flipped
+    pub fn convert_normal(&self, x: f64, y: f64, z: f64) -> Vector3 {
+        let normal: Vector3 = self.convert_direction(x, y, z);
+        if self.flip_winding { -1.0 * normal } else { normal }
+    }
+
+    pub fn convert_uv(&self, u: f64, v: f64) -> Vector2 {
+        if self.flip_v { Vector2::new(u, 1.0 - v) } else { Vector2::new(u, v) }
+    }
+
+    // Whether converted triangles have to be wound (p1, p3, p2) for `Tri::compute_face_normal` to point out
+    // of the front face. Counter-clockwise fronts come out right as is when the conversion mirrors the mesh.
+    pub fn reverses_winding(&self) -> bool {
+        self.mirrors() == self.flip_winding
+    }
+
+    pub fn face_normal(&self, p1: Vector3, p2: Vector3, p3: Vector3) -> Vector3 {
+        if self.reverses_winding() {
+            Tri::compute_face_normal(p1, p3, p2)
+        } else {
+            Tri::compute_face_normal(p1, p2, p3)
+        }
+    }
+
+    // Inverse of convert_point, takes a point in renderer space back into this convention
+    pub fn export_point(&self, point: Vector3) -> Vector3 {
+        self.unswap_axes(point) / self.scale
+    }
+
+    pub fn export_direction(&self, direction: Vector3) -> Vector3 {
+        self.scale.signum() * self.unswap_axes(direction)
+    }
+
+    pub fn export_uv(&self, uv: Vector2) -> Vector2 {
+        self.convert_uv(uv.x, uv.y)
+    }
+
+    fn mirrors(&self) -> bool {
+        // Going from a right handed system to the left handed renderer space always mirrors
+        (self.handedness == Handedness::Right) != (self.scale < 0.0)
+    }
+
+    fn swap_axes(&self, x: f64, y: f64, z: f64) -> Vector3 {
+        match (self.up_axis, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => Vector3::new(x, z, y),
+            (UpAxis::Y, Handedness::Left) => Vector3::new(x, -z, y),
+            (UpAxis::Z, Handedness::Right) => Vector3::new(x, -y, z),
+            (UpAxis::Z, Handedness::Left) => Vector3::new(x, y, z)
+        }
+    }
+
+    fn unswap_axes(&self, v: Vector3) -> Vector3 {
+        match (self.up_axis, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => Vector3::new(v.x, v.z, v.y),
+            (UpAxis::Y, Handedness::Left) => Vector3::new(v.x, v.z, -v.y),
+            (UpAxis::Z, Handedness::Right) => Vector3::new(v.x, -v.y, v.z),
+            (UpAxis::Z, Handedness::Left) => v
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        a.distance(b) < 1e-9
+    }
+
+    #[test]
+    fn converts_each_convention_into_renderer_space() {
+        // The renderer space point (3, 1, 2) as written in each convention, 2 along its up axis
+        let cases: [(UpAxis, Handedness, [f64; 3]); 4] = [
+            (UpAxis::Y, Handedness::Right, [3.0, 2.0, 1.0]),
+            (UpAxis::Y, Handedness::Left, [3.0, 2.0, -1.0]),
+            (UpAxis::Z, Handedness::Right, [3.0, -1.0, 2.0]),
+            (UpAxis::Z, Handedness::Left, [3.0, 1.0, 2.0])
+        ];
+        for (up_axis, handedness, [x, y, z]) in cases {
+            let options: ImportOptions = ImportOptions::new(up_axis, handedness, 2.0, false, false);
+            let point: Vector3 = options.convert_point(x, y, z);
+            assert!(close(point, Vector3::new(6.0, 2.0, 4.0)), "{:?} {:?}", up_axis, handedness);
+            assert!(close(options.export_point(point), Vector3::new(x, y, z)), "{:?} {:?}", up_axis, handedness);
+            assert!(close(options.convert_direction(x, y, z), Vector3::new(3.0, 1.0, 2.0)), "{:?} {:?}", up_axis, handedness);
+        }
+    }
+
+    #[test]
+    fn picks_the_convention_of_the_file_format() {
+        for (file, up_axis) in [("a.obj", UpAxis::Y), ("a.gltf", UpAxis::Y), ("a.glb", UpAxis::Y), ("a.stl", UpAxis::Z), ("a.ply", UpAxis::Z)] {
+            let options: ImportOptions = ImportOptions::for_file(file);
+            assert_eq!((options.up_axis, options.handedness), (up_axis, Handedness::Right), "{}", file);
+        }
+    }
+
+    #[test]
+    fn flipping_the_winding_turns_stored_normals_around() {
+        let normal: Vector3 = ImportOptions::z_up().convert_normal(0.0, 0.0, 1.0);
+        let flipped: Vector3 = ImportOptions::z_up().with_flip_winding(true).convert_normal(0.0, 0.0, 1.0);
+        assert!(close(normal, Vector3::new(0.0, 0.0, 1.0)));
+        assert!(close(flipped, Vector3::new(0.0, 0.0, -1.0)));
+        assert!(ImportOptions::z_up().reverses_winding() != ImportOptions::z_up().with_flip_winding(true).reverses_winding());
+    }
+}
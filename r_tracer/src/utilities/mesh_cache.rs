@@ -0,0 +1,629 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::spacial::tri::Tri;
+use crate::spacial::bvh::BVH;
+use crate::spacial::mesh_object::MeshObject;
+use crate::spacial::motion::Motion;
+use crate::datatypes::material::Material;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::utilities::file_utilities::{load_model, load_model_with_materials, mtl_texture_files};
+use crate::utilities::gltf_utilities::gltf_external_files;
+use crate::utilities::import_options::{ImportOptions, UpAxis, Handedness};
+use crate::error::RTracerError;
+
+
+// Bumped whenever the layout of a cache file or the output of a loader changes, older files are then rebuilt
+pub const CACHE_VERSION: u32 = 5;
+const MAGIC: &[u8; 8] = b"RTCACHE\0";
+pub(crate) const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+// BVH::new halves the triangles at every level, a damaged file nesting deeper would only overflow the stack
+const MAX_BVH_DEPTH: usize = 64;
+
+// 64 bit FNV-1a, stable between runs and Rust versions unlike the std hashers
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash: u64, &byte: &u8| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum EntryKind {
+    Model = 1,
+    ModelWithMaterials = 2,
+    Bvh = 3
+}
+
+// Directory of preprocessed meshes and BVHs. Entries are named after a hash of everything that went into them,
+// so a changed model file or import option simply misses and is rebuilt. The key covers the model file and every
+// file the importer resolves from it: the material libraries of an OBJ and their textures, and the external
+// buffers and images of a glTF.
+#[derive(Clone, Debug)]
+pub struct MeshCache {
+    pub directory: PathBuf
+}
+
+impl MeshCache {
+    pub fn new(directory: &str) -> MeshCache {
+        MeshCache { directory: PathBuf::from(directory) }
+    }
+
+    // Same as file_utilities::load_model, reading the triangles from the cache when they are there
+    pub fn load_model(&self, file_path: &str, material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+        let mut key: CacheWriter = self.source_key(EntryKind::Model, file_path, options)?;
+        key.material(&material);
+        let path: PathBuf = self.entry_path(key.hash(), "mesh");
+
+        if let Some(tris) = self.read_entry(&path, EntryKind::Model, |reader: &mut CacheReader| reader.tris()) {
+            return Ok(tris)
+        }
+        let tris: Vec<Tri> = load_model(file_path, material, options)?;
+        let mut writer: CacheWriter = CacheWriter::new(EntryKind::Model);
+        writer.tris(&tris);
+        self.write_entry(&path, writer);
+        Ok(tris)
+    }
+
+    // Same as file_utilities::load_model_with_materials. The textures the model brings along are stored too,
+    // and their indices are shifted to wherever they land in `texture_maps` on later runs.
+    pub fn load_model_with_materials(&self, file_path: &str, texture_maps: &mut Vec<Vector2D<Color>>,
+        default_material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+        let mut key: CacheWriter = self.source_key(EntryKind::ModelWithMaterials, file_path, options)?;
+        key.material(&default_material);
+        let path: PathBuf = self.entry_path(key.hash(), "mesh");
+
+        let cached = self.read_entry(&path, EntryKind::ModelWithMaterials, |reader: &mut CacheReader| {
+            let base: usize = reader.usize()?;
+            let textures: Vec<Vector2D<Color>> = reader.list(CacheReader::texture)?;
+            Ok((base, textures, reader.tris()?))
+        });
+        if let Some((base, textures, mut tris)) = cached {
+            let offset: usize = texture_maps.len();
+            for tri in &mut tris {
+                shift_map_indices(&mut tri.material, base, offset);
+            }
+            texture_maps.extend(textures);
+            return Ok(tris)
+        }
+
+        let base: usize = texture_maps.len();
+        let tris: Vec<Tri> = load_model_with_materials(file_path, texture_maps, default_material, options)?;
+        let mut writer: CacheWriter = CacheWriter::new(EntryKind::ModelWithMaterials);
+        writer.usize(base);
+        writer.usize(texture_maps.len() - base);
+        for texture in &texture_maps[base..] {
+            writer.texture(texture);
+        }
+        writer.tris(&tris);
+        self.write_entry(&path, writer);
+        Ok(tris)
+    }
+
+    // BVH::new, keyed by the triangles of the meshes
+    pub fn load_bvh(&self, mesh_objects: &[MeshObject]) -> BVH {
+        let mut key: CacheWriter = CacheWriter::key(EntryKind::Bvh);
+        for mesh in mesh_objects {
+            key.usize(mesh.tris.len());
+            for tri in &mesh.tris {
+                key.tri_geometry(tri);
+            }
+        }
+        let path: PathBuf = self.entry_path(key.hash(), "bvh");
+
+        if let Some(bvh) = self.read_entry(&path, EntryKind::Bvh, |reader: &mut CacheReader| reader.bvh()) {
+            return bvh
+        }
+        let bvh: BVH = BVH::new(mesh_objects);
+        let mut writer: CacheWriter = CacheWriter::new(EntryKind::Bvh);
+        writer.bvh(&bvh);
+        self.write_entry(&path, writer);
+        bvh
+    }
+
+    // Removes every cache entry, leaving other files in the directory alone
+    pub fn clear(&self) -> Result<(), RTracerError> {
+        let directory: String = self.directory.to_string_lossy().into_owned();
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(())
+        };
+        for entry in entries {
+            let path: PathBuf = entry.map_err(|err| RTracerError::io(&directory, err))?.path();
+            if path.extension().is_some_and(|extension| extension == "mesh" || extension == "bvh") {
+                fs::remove_file(&path).map_err(|err| RTracerError::io(&path.to_string_lossy(), err))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn source_key(&self, kind: EntryKind, file_path: &str, options: &ImportOptions) -> Result<CacheWriter, RTracerError> {
+        let mut key: CacheWriter = CacheWriter::key(kind);
+        key.bytes(&fs::read(file_path).map_err(|err| RTracerError::io(file_path, err))?);
+        key.bytes(file_path.rsplit('.').next().unwrap_or("").to_lowercase().as_bytes());
+        key.import_options(options);
+        // A missing file hashes as empty, the importer reports it on the miss
+        for source in source_files(file_path) {
+            key.bytes(&fs::read(&source).unwrap_or_default());
+        }
+        Ok(key)
+    }
+
+    fn entry_path(&self, hash: u64, extension: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.{}", hash, extension))
+    }
+
+    // A missing, outdated or damaged entry is treated as a miss
+    fn read_entry<T>(&self, path: &Path, kind: EntryKind,
+        read: impl FnOnce(&mut CacheReader) -> Result<T, String>) -> Option<T> {
+        let bytes: Vec<u8> = fs::read(path).ok()?;
+        let start_time = Instant::now();
+        let mut reader: CacheReader = CacheReader { bytes: &bytes, position: 0 };
+        let result: Result<T, String> = reader.header(kind).and_then(|_| read(&mut reader));
+        match result {
+            Ok(value) => {
+                println!("Loaded {} from cache in {} seconds", path.display(), start_time.elapsed().as_millis() as f64 / 1000.0);
+                Some(value)
+            },
+            Err(message) => {
+                eprintln!("Ignoring cache entry {}: {}", path.display(), message);
+                None
+            }
+        }
+    }
+
+    // A cache that cannot be written only costs the time to rebuild next run, so failures are reported and ignored
+    fn write_entry(&self, path: &Path, writer: CacheWriter) {
+        // Written to the side first so an interrupted run never leaves a truncated entry behind
+        let temporary: PathBuf = path.with_extension("tmp");
+        let result = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&temporary, writer.bytes))
+            .and_then(|_| fs::rename(&temporary, path));
+        if let Err(err) = result {
+            eprintln!("{}", RTracerError::io(&path.to_string_lossy(), err));
+        }
+    }
+}
+
+// Files other than the model itself that its importer reads, resolved the way the importer resolves them
+fn source_files(file_path: &str) -> Vec<PathBuf> {
+    if file_path.ends_with(".gltf") || file_path.ends_with(".glb") {
+        return gltf_external_files(file_path)
+    }
+    if !file_path.ends_with(".obj") {
+        return vec![]
+    }
+    let directory: &Path = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let content: String = fs::read_to_string(file_path).unwrap_or_default();
+    let mut files: Vec<PathBuf> = vec![];
+    for library in content.lines().filter_map(|line: &str| line.trim().strip_prefix("mtllib ")) {
+        let library: PathBuf = directory.join(library.trim());
+        let library_content: String = fs::read_to_string(&library).unwrap_or_default();
+        files.extend(mtl_texture_files(&library_content, library.parent().unwrap_or(directory)));
+        files.push(library);
+    }
+    files
+}
+
+fn shift_map_indices(material: &mut Material, base: usize, offset: usize) {
+    let shift = |index: &mut Option<usize>| {
+        if let Some(value) = index {
+            if *value >= base {
+                *value = *value - base + offset;
+            }
+        }
+    };
+    shift(&mut material.diffuse_color_map_index);
+    shift(&mut material.emission_color_map_index);
+    shift(&mut material.specular_color_map_index);
+    shift(&mut material.dielectric_color_map_index);
+    shift(&mut material.normal_map_index);
+    shift(&mut material.smoothness_map_index);
+    shift(&mut material.specular_map_index);
+}
+
+// Little endian encoding of the cached types. The same encoding doubles as the input of the cache key,
+// keys are only hashed and never held in memory.
+struct CacheWriter {
+    bytes: Vec<u8>,
+    keep_bytes: bool,
+    hash: u64
+}
+
+impl CacheWriter {
+    fn new(kind: EntryKind) -> CacheWriter {
+        let mut writer: CacheWriter = CacheWriter { bytes: vec![], keep_bytes: true, hash: FNV_OFFSET };
+        writer.raw(MAGIC);
+        writer.raw(&CACHE_VERSION.to_le_bytes());
+        writer.raw(&[kind as u8]);
+        writer
+    }
+
+    fn key(kind: EntryKind) -> CacheWriter {
+        let mut writer: CacheWriter = CacheWriter::new(kind);
+        writer.keep_bytes = false;
+        writer.bytes = vec![];
+        writer
+    }
+
+    fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        self.hash = fnv1a(self.hash, bytes);
+        if self.keep_bytes {
+            self.bytes.extend_from_slice(bytes);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.raw(bytes);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.raw(&[value as u8]);
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.raw(&(value as u64).to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.raw(&value.to_le_bytes());
+    }
+
+    fn index(&mut self, index: Option<usize>) {
+        self.raw(&index.map_or(-1, |index: usize| index as i64).to_le_bytes());
+    }
+
+    fn vector3(&mut self, v: Vector3) {
+        self.f64(v.x);
+        self.f64(v.y);
+        self.f64(v.z);
+    }
+
+    fn vector2(&mut self, v: Vector2) {
+        self.f64(v.x);
+        self.f64(v.y);
+    }
+
+    fn color(&mut self, color: Color) {
+        self.f64(color.red);
+        self.f64(color.green);
+        self.f64(color.blue);
+    }
+
+    fn import_options(&mut self, options: &ImportOptions) {
+        self.bool(options.up_axis == UpAxis::Z);
+        self.bool(options.handedness == Handedness::Left);
+        self.f64(options.scale);
+        self.bool(options.flip_winding);
+        self.bool(options.flip_v);
+    }
+
+    fn material(&mut self, material: &Material) {
+        for color in [material.diffuse_color, material.emission_color, material.specular_color, material.dielectric_color] {
+            self.color(color);
+        }
+        for value in [material.emission_strength, material.smoothness, material.specular, material.dielectric,
+            material.index_of_refraction, material.normal_strength] {
+            self.f64(value);
+        }
+        self.bool(material.visible);
+        self.bool(material.use_vertex_colors);
+        for index in [material.diffuse_color_map_index, material.emission_color_map_index,
+            material.specular_color_map_index, material.dielectric_color_map_index, material.normal_map_index,
+            material.smoothness_map_index, material.specular_map_index] {
+            self.index(index);
+        }
+    }
+
+    // Everything but the bounding box, which Tri::get_bounding_box pads by a random amount and so differs
+    // between runs for the same mesh
+    fn tri_geometry(&mut self, tri: &Tri) {
+        for v in [tri.p1, tri.p2, tri.p3, tri.p1_normal, tri.p2_normal, tri.p3_normal,
+            tri.p1_tangent, tri.p2_tangent, tri.p3_tangent, tri.normal] {
+            self.vector3(v);
+        }
+        self.bool(tri.motion.is_some());
+        if let Some(motion) = tri.motion {
+            for v in [motion.translation, motion.rotation, motion.pivot] {
+                self.vector3(v);
+            }
+        }
+        for v in [tri.p1_texture, tri.p2_texture, tri.p3_texture] {
+            self.vector2(v);
+        }
+        for color in [tri.p1_color, tri.p2_color, tri.p3_color] {
+            self.color(color);
+        }
+        self.f64(tri.bitangent_sign);
+        self.bool(tri.smooth_shading);
+        self.bool(tri.is_empty);
+        self.usize(tri.object_id);
+        self.usize(tri.material_id);
+        self.material(&tri.material);
+    }
+
+    fn tri(&mut self, tri: &Tri) {
+        self.tri_geometry(tri);
+        for v in [tri.bounding_box.0, tri.bounding_box.1, tri.bounding_box_center] {
+            self.vector3(v);
+        }
+    }
+
+    fn tris(&mut self, tris: &[Tri]) {
+        self.usize(tris.len());
+        for tri in tris {
+            self.tri(tri);
+        }
+    }
+
+    // Stored as f32, which is plenty for texture data and halves the size of the entry
+    fn texture(&mut self, texture: &Vector2D<Color>) {
+        self.usize(texture.width);
+        self.usize(texture.height);
+        for color in &texture.data {
+            for value in [color.red, color.green, color.blue] {
+                self.raw(&(value as f32).to_le_bytes());
+            }
+        }
+    }
+
+    // Depth first, the empty triangles of inner nodes are left out
+    fn bvh(&mut self, bvh: &BVH) {
+        self.vector3(bvh.bb_corner_1);
+        self.vector3(bvh.bb_corner_2);
+        self.f64(bvh.bounding_box_surface_area);
+        self.bool(bvh.is_leaf);
+        self.bool(!bvh.tri.is_empty);
+        if !bvh.tri.is_empty {
+            self.tri(&bvh.tri);
+        }
+        for child in [&bvh.bvh_obj_1, &bvh.bvh_obj_2] {
+            self.bool(child.is_some());
+            if let Some(child) = child {
+                self.bvh(child);
+            }
+        }
+    }
+}
+
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> CacheReader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let end: usize = self.position + N;
+        let slice: &[u8] = self.bytes.get(self.position..end).ok_or_else(|| String::from("unexpected end of file"))?;
+        self.position = end;
+        Ok(slice.try_into().unwrap_or([0; N]))
+    }
+
+    fn header(&mut self, kind: EntryKind) -> Result<(), String> {
+        if &self.take::<8>()? != MAGIC {
+            return Err(String::from("not a cache file"))
+        }
+        let version: u32 = u32::from_le_bytes(self.take()?);
+        if version != CACHE_VERSION {
+            return Err(format!("written by cache version {}, expected {}", version, CACHE_VERSION))
+        }
+        if self.take::<1>()?[0] != kind as u8 {
+            return Err(String::from("wrong kind of entry"))
+        }
+        Ok(())
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.take::<1>()?[0] != 0)
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        Ok(u64::from_le_bytes(self.take()?) as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn index(&mut self) -> Result<Option<usize>, String> {
+        let index: i64 = i64::from_le_bytes(self.take()?);
+        Ok(if index < 0 { None } else { Some(index as usize) })
+    }
+
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let len: usize = self.usize()?;
+        // Guards against allocating for a length read out of a damaged file
+        let mut items: Vec<T> = Vec::with_capacity(len.min(self.bytes.len() - self.position));
+        for _ in 0..len {
+            items.push(read(self)?);
+        }
+        Ok(items)
+    }
+
+    fn vector3(&mut self) -> Result<Vector3, String> {
+        Ok(Vector3::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    fn vector2(&mut self) -> Result<Vector2, String> {
+        Ok(Vector2::new(self.f64()?, self.f64()?))
+    }
+
+    fn color(&mut self) -> Result<Color, String> {
+        Ok(Color::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    fn material(&mut self) -> Result<Material, String> {
+        let mut material: Material = Material::new(
+            self.color()?, self.color()?, self.color()?, self.color()?,
+            self.f64()?, self.f64()?, self.f64()?, self.f64()?, self.f64()?, self.f64()?,
+            self.bool()?, None, None, None, None, None, None, None
+        );
+        material.use_vertex_colors = self.bool()?;
+        material.diffuse_color_map_index = self.index()?;
+        material.emission_color_map_index = self.index()?;
+        material.specular_color_map_index = self.index()?;
+        material.dielectric_color_map_index = self.index()?;
+        material.normal_map_index = self.index()?;
+        material.smoothness_map_index = self.index()?;
+        material.specular_map_index = self.index()?;
+        Ok(material)
+    }
+
+    fn tri(&mut self) -> Result<Tri, String> {
+        let mut tri: Tri = Tri::empty();
+        for v in [&mut tri.p1, &mut tri.p2, &mut tri.p3, &mut tri.p1_normal, &mut tri.p2_normal, &mut tri.p3_normal,
+            &mut tri.p1_tangent, &mut tri.p2_tangent, &mut tri.p3_tangent, &mut tri.normal] {
+            *v = self.vector3()?;
+        }
+        if self.bool()? {
+            tri.motion = Some(Motion::new(self.vector3()?, self.vector3()?, self.vector3()?));
+        }
+        for v in [&mut tri.p1_texture, &mut tri.p2_texture, &mut tri.p3_texture] {
+            *v = self.vector2()?;
+        }
+        for color in [&mut tri.p1_color, &mut tri.p2_color, &mut tri.p3_color] {
+            *color = self.color()?;
+        }
+        tri.bitangent_sign = self.f64()?;
+        tri.smooth_shading = self.bool()?;
+        tri.is_empty = self.bool()?;
+        tri.object_id = self.usize()?;
+        tri.material_id = self.usize()?;
+        tri.material = self.material()?;
+        for v in [&mut tri.bounding_box.0, &mut tri.bounding_box.1, &mut tri.bounding_box_center] {
+            *v = self.vector3()?;
+        }
+        Ok(tri)
+    }
+
+    fn tris(&mut self) -> Result<Vec<Tri>, String> {
+        self.list(Self::tri)
+    }
+
+    fn texture(&mut self) -> Result<Vector2D<Color>, String> {
+        let width: usize = self.usize()?;
+        let height: usize = self.usize()?;
+        if width.saturating_mul(height).saturating_mul(12) > self.bytes.len() - self.position {
+            return Err(String::from("unexpected end of file"))
+        }
+        let mut texture: Vector2D<Color> = Vector2D::new(width, height, Color::black());
+        for color in &mut texture.data {
+            let mut channel = || -> Result<f64, String> { Ok(f32::from_le_bytes(self.take()?) as f64) };
+            *color = Color::new(channel()?, channel()?, channel()?);
+        }
+        Ok(texture)
+    }
+
+    fn bvh(&mut self) -> Result<BVH, String> {
+        self.bvh_at(0)
+    }
+
+    fn bvh_at(&mut self, depth: usize) -> Result<BVH, String> {
+        let mut bvh: BVH = BVH::empty();
+        bvh.bb_corner_1 = self.vector3()?;
+        bvh.bb_corner_2 = self.vector3()?;
+        bvh.bounding_box_surface_area = self.f64()?;
+        bvh.is_leaf = self.bool()?;
+        if self.bool()? {
+            bvh.tri = self.tri()?;
+        }
+        if self.bool()? {
+            bvh.bvh_obj_1 = Some(Box::new(self.bvh_below(depth)?));
+        }
+        if self.bool()? {
+            bvh.bvh_obj_2 = Some(Box::new(self.bvh_below(depth)?));
+        }
+        Ok(bvh)
+    }
+
+    fn bvh_below(&mut self, depth: usize) -> Result<BVH, String> {
+        if depth >= MAX_BVH_DEPTH {
+            return Err(String::from("BVH is nested too deeply"))
+        }
+        self.bvh_at(depth + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::file_utilities::save_vector2d_as_png_with;
+    use crate::utilities::postprocessing::PostProcessing;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory: PathBuf = std::env::temp_dir().join(format!("r_tracer_cache_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn load_textured_quad(cache: &MeshCache, obj_path: &str) -> Vector2D<Color> {
+        let mut texture_maps: Vec<Vector2D<Color>> = vec![];
+        cache.load_model_with_materials(obj_path, &mut texture_maps, Material::empty(), &ImportOptions::y_up()).unwrap();
+        texture_maps.pop().unwrap()
+    }
+
+    #[test]
+    fn edited_textures_miss_the_cache() {
+        let directory: PathBuf = temp_dir("texture");
+        let obj_path: String = directory.join("quad.obj").to_string_lossy().into_owned();
+        let texture_path: String = directory.join("checker.png").to_string_lossy().into_owned();
+        fs::write(&obj_path, "mtllib quad.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nusemtl paint\nf 1/1 2/2 3/3\n").unwrap();
+        fs::write(directory.join("quad.mtl"), "newmtl paint\nmap_Kd checker.png\n").unwrap();
+        let cache: MeshCache = MeshCache::new(&directory.join("cache").to_string_lossy());
+
+        let red: Vector2D<Color> = Vector2D::new(2, 2, Color::new(1.0, 0.0, 0.0));
+        save_vector2d_as_png_with(&red, &texture_path, PostProcessing::None).unwrap();
+        assert!(load_textured_quad(&cache, &obj_path).data[0] == red.data[0]);
+
+        let blue: Vector2D<Color> = Vector2D::new(2, 2, Color::new(0.0, 0.0, 1.0));
+        save_vector2d_as_png_with(&blue, &texture_path, PostProcessing::None).unwrap();
+        let reloaded: Vector2D<Color> = load_textured_quad(&cache, &obj_path);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(reloaded.data[0] == blue.data[0]);
+    }
+
+    #[test]
+    fn gltf_sources_include_external_buffers_and_images() {
+        let directory: PathBuf = temp_dir("gltf");
+        let gltf_path: String = directory.join("scene.gltf").to_string_lossy().into_owned();
+        fs::write(&gltf_path, r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [
+                { "byteLength": 4, "uri": "geometry.bin" },
+                { "byteLength": 4, "uri": "data:application/octet-stream;base64,AAAAAA==" }
+            ],
+            "images": [{ "uri": "wood%20grain.png" }]
+        }"#).unwrap();
+        let sources: Vec<PathBuf> = source_files(&gltf_path);
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(sources, vec![directory.join("geometry.bin"), directory.join("wood grain.png")]);
+    }
+
+    #[test]
+    fn overly_deep_bvhs_are_rejected() {
+        let mut bvh: BVH = BVH::empty();
+        for _ in 0..MAX_BVH_DEPTH + 1 {
+            let mut parent: BVH = BVH::empty();
+            parent.bvh_obj_1 = Some(Box::new(bvh));
+            bvh = parent;
+        }
+        let mut writer: CacheWriter = CacheWriter::new(EntryKind::Bvh);
+        writer.bvh(&bvh);
+
+        let mut reader: CacheReader = CacheReader { bytes: &writer.bytes, position: 0 };
+        reader.header(EntryKind::Bvh).unwrap();
+        assert_eq!(reader.bvh().err().unwrap(), "BVH is nested too deeply");
+
+        // One level less still reads
+        let mut writer: CacheWriter = CacheWriter::new(EntryKind::Bvh);
+        writer.bvh(bvh.bvh_obj_1.as_ref().unwrap());
+        let mut reader: CacheReader = CacheReader { bytes: &writer.bytes, position: 0 };
+        reader.header(EntryKind::Bvh).unwrap();
+        assert!(reader.bvh().is_ok());
+    }
+}
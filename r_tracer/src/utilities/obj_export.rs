@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+use crate::spacial::scene::Scene;
+use crate::spacial::mesh_object::MeshObject;
+use crate::datatypes::material::Material;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2::Vector2;
+use crate::datatypes::color::Color;
+use crate::datatypes::vector2d::Vector2D;
+use crate::utilities::import_options::{ImportOptions, UpAxis, Handedness};
+use crate::utilities::file_utilities::{save_vector2d_as_exr, save_vector2d_as_png16, ExrPrecision};
+use crate::utilities::postprocessing::PostProcessing;
+use crate::error::RTracerError;
+
+
+// Writes every triangle of the scene, with its vertex normals, UVs and material, to an OBJ file and an MTL
+// file next to it. `options` is the convention to write in: loading the files back with the same options
+// gives the same triangles. Texture maps are written next to the MTL as 16 bit PNGs, or as EXRs when they
+// hold values above 1.
+pub fn export_obj(scene: &Scene, file_path: &str, options: &ImportOptions) -> Result<(), RTracerError> {
+    println!("Exporting .obj file: {}", file_path);
+    let obj_path: &Path = Path::new(file_path);
+    let directory: &Path = obj_path.parent().unwrap_or(Path::new(""));
+    let stem: String = obj_path.file_stem().map_or(String::from("scene"), |stem| stem.to_string_lossy().into_owned());
+    let mtl_file: String = format!("{}.mtl", stem);
+
+    let materials: Vec<Material> = MeshObject::unique_materials(&scene.meshes);
+    let texture_files: Vec<String> = export_textures(scene, directory, &stem)?;
+
+    let mut vertices: ObjIndex = ObjIndex::new();
+    let mut textures: ObjIndex = ObjIndex::new();
+    let mut normals: ObjIndex = ObjIndex::new();
+    let mut faces: String = String::new();
+
+    for (mesh_index, mesh) in scene.meshes.iter().enumerate() {
+        let name: String = if mesh.name.is_empty() { format!("mesh_{}", mesh_index) } else { mesh.name.clone() };
+        let _ = writeln!(faces, "o {}", name);
+        let _ = writeln!(faces, "s {}", if mesh.smooth_shading { "1" } else { "off" });
+
+        let mut current_material: Option<usize> = None;
+        for tri in &mesh.tris {
+            let material_index: usize = materials.iter().position(|material: &Material| *material == tri.material).unwrap_or(0);
+            if current_material != Some(material_index) {
+                let _ = writeln!(faces, "usemtl material_{}", material_index);
+                current_material = Some(material_index);
+            }
+
+            let corners: [(Vector3, Vector2, Vector3); 3] = [
+                (tri.p1, tri.p1_texture, tri.p1_normal),
+                (tri.p2, tri.p2_texture, tri.p2_normal),
+                (tri.p3, tri.p3_texture, tri.p3_normal)
+            ];
+            faces.push('f');
+            for (position, texture, normal) in corners {
+                let position: Vector3 = options.export_point(position);
+                let texture: Vector2 = options.export_uv(texture);
+                let normal: Vector3 = options.export_direction(normal);
+                let _ = write!(faces, " {}/{}/{}",
+                    vertices.index_of("v", &[position.x, position.y, position.z]),
+                    textures.index_of("vt", &[texture.x, texture.y]),
+                    normals.index_of("vn", &[normal.x, normal.y, normal.z]));
+            }
+            faces.push('\n');
+        }
+    }
+
+    let mut obj: String = format!("# Exported from RTracer, {} triangles\nmtllib {}\n", triangle_count(scene), mtl_file);
+    obj.push_str(&vertices.lines);
+    obj.push_str(&textures.lines);
+    obj.push_str(&normals.lines);
+    obj.push_str(&faces);
+    fs::write(file_path, obj).map_err(|err| RTracerError::io(file_path, err))?;
+
+    let mtl_path: String = directory.join(&mtl_file).to_string_lossy().into_owned();
+    let mtl: String = materials.iter().enumerate()
+        .map(|(index, material)| material_to_mtl(index, material, &texture_files))
+        .collect::<Vec<String>>()
+        .join("\n");
+    fs::write(&mtl_path, mtl).map_err(|err| RTracerError::io(&mtl_path, err))?;
+
+    println!("Done");
+    Ok(())
+}
+
+// Writes the scene as the renderer sees it, without converting back to an asset convention
+pub fn export_obj_raw(scene: &Scene, file_path: &str) -> Result<(), RTracerError> {
+    export_obj(scene, file_path, &ImportOptions::new(UpAxis::Z, Handedness::Left, 1.0, false, false))
+}
+
+fn triangle_count(scene: &Scene) -> usize {
+    scene.meshes.iter().map(|mesh: &MeshObject| mesh.tris.len()).sum()
+}
+
+// Every texture map is written once, materials refer to them by index
+fn export_textures(scene: &Scene, directory: &Path, stem: &str) -> Result<Vec<String>, RTracerError> {
+    let mut files: Vec<String> = vec![];
+    for (index, texture) in scene.texture_maps.iter().enumerate() {
+        let is_hdr: bool = texture.data.iter()
+            .any(|color: &Color| color.red > 1.0 || color.green > 1.0 || color.blue > 1.0);
+        let file: String = format!("{}_texture_{}.{}", stem, index, if is_hdr { "exr" } else { "png" });
+        let path: String = directory.join(&file).to_string_lossy().into_owned();
+        write_texture(texture, &path, is_hdr)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+fn write_texture(texture: &Vector2D<Color>, path: &str, is_hdr: bool) -> Result<(), RTracerError> {
+    if is_hdr {
+        save_vector2d_as_exr(texture, path, ExrPrecision::Float, PostProcessing::None)
+    } else {
+        save_vector2d_as_png16(texture, path, PostProcessing::None)
+    }
+}
+
+// Inverse of the MTL import. Visibility, vertex colors, the dielectric color map and the specular strength
+// map have no MTL statement and are dropped.
+fn material_to_mtl(index: usize, material: &Material, texture_files: &[String]) -> String {
+    let mut mtl: String = format!("newmtl material_{}\n", index);
+    let color = |color: Color| -> String { format!("{} {} {}", color.red, color.green, color.blue) };
+
+    let _ = writeln!(mtl, "Kd {}", color(material.diffuse_color));
+    let _ = writeln!(mtl, "Ks {}", color(specular_for_mtl(material)));
+    let _ = writeln!(mtl, "Pm {}", material.specular);
+    let _ = writeln!(mtl, "Ke {}", color(material.emission_color * material.emission_strength));
+    let _ = writeln!(mtl, "Tf {}", color(material.dielectric_color));
+    // Pr is what the importer reads, Ns is kept for tools without the PBR extension
+    let _ = writeln!(mtl, "Ns {}", material.smoothness * material.smoothness * 1000.0);
+    let _ = writeln!(mtl, "Pr {}", 1.0 - material.smoothness);
+    let _ = writeln!(mtl, "Ni {}", material.index_of_refraction);
+    let _ = writeln!(mtl, "d {}", 1.0 - material.dielectric);
+
+    let maps: [(&str, Option<usize>); 4] = [
+        ("map_Kd", material.diffuse_color_map_index),
+        ("map_Ks", material.specular_color_map_index),
+        ("map_Ke", material.emission_color_map_index),
+        ("map_Ns", material.smoothness_map_index)
+    ];
+    for (statement, map_index) in maps {
+        if let Some(file) = map_index.and_then(|map_index: usize| texture_files.get(map_index)) {
+            let _ = writeln!(mtl, "{} {}", statement, file);
+        }
+    }
+    if let Some(file) = material.normal_map_index.and_then(|map_index: usize| texture_files.get(map_index)) {
+        let _ = writeln!(mtl, "norm -bm {} {}", material.normal_strength, file);
+    }
+
+    mtl
+}
+
+// The importer tints the specular color towards the diffuse color by Pm, so the tint is undone here.
+// Colors that cannot be reached that way (fully metallic or negative channels) are written as is.
+fn specular_for_mtl(material: &Material) -> Color {
+    let metallic: f64 = material.specular.clamp(0.0, 1.0);
+    if metallic >= 1.0 {
+        return material.specular_color
+    }
+    let specular: Color = (material.specular_color - material.diffuse_color * metallic) * (1.0 / (1.0 - metallic));
+    if specular.red < 0.0 || specular.green < 0.0 || specular.blue < 0.0 {
+        return material.specular_color
+    }
+    specular
+}
+
+// Deduplicates v, vt and vn statements, OBJ indices start at 1
+struct ObjIndex {
+    indices: HashMap<Vec<u64>, usize>,
+    lines: String
+}
+
+impl ObjIndex {
+    fn new() -> ObjIndex {
+        ObjIndex { indices: HashMap::new(), lines: String::new() }
+    }
+
+    fn index_of(&mut self, statement: &str, values: &[f64]) -> usize {
+        // -0.0 and 0.0 print differently but are the same vertex
+        let key: Vec<u64> = values.iter().map(|value: &f64| (value + 0.0).to_bits()).collect();
+        if let Some(&index) = self.indices.get(&key) {
+            return index
+        }
+        let index: usize = self.indices.len() + 1;
+        let _ = writeln!(self.lines, "{} {}", statement,
+            values.iter().map(|value: &f64| (value + 0.0).to_string()).collect::<Vec<String>>().join(" "));
+        self.indices.insert(key, index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spacial::tri::Tri;
+    use crate::utilities::file_utilities::load_model_with_materials;
+
+    // The OBJ loader reads single precision floats
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    fn close_vector(a: Vector3, b: Vector3) -> bool {
+        a.distance(b) < 1e-6
+    }
+
+    fn close_color(a: Color, b: Color) -> bool {
+        close(a.red, b.red) && close(a.green, b.green) && close(a.blue, b.blue)
+    }
+
+    // A textured, partly metallic and partly transmissive quad with its own normals and UVs
+    fn scene() -> Scene {
+        let diffuse_color: Color = Color::new(0.6, 0.4, 0.2);
+        let material: Material = Material::builder()
+            .with_diffuse_color(diffuse_color)
+            // What the importer makes of Ks 0.9 0.8 0.7 and Pm 0.3
+            .with_specular_color(Color::lerp(Color::new(0.9, 0.8, 0.7), diffuse_color, 0.3))
+            .with_specular(0.3)
+            .with_emission(Color::new(0.5, 0.25, 0.125), 1.0)
+            .with_dielectric_color(Color::new(0.75, 1.0, 0.5))
+            .with_dielectric(0.2)
+            .with_smoothness(0.6)
+            .with_index_of_refraction(1.4)
+            .with_normal_strength(0.5)
+            .with_diffuse_color_map(0)
+            .with_normal_map(0)
+            .build(1)
+            .unwrap();
+        let (a, b, c, d) = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(0.0, 2.0, 1.0), Vector3::new(0.0, 0.0, 1.0));
+        let normal: Vector3 = Vector3::new(-1.0, 0.0, 0.0);
+        let tilted: Vector3 = Vector3::new(-0.6, 0.0, 0.8);
+        let (ta, tb, tc, td) = (Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 0.5), Vector2::new(0.0, 0.5));
+        let quad: MeshObject = MeshObject::new(vec![
+            Tri::new(a, b, c, normal, normal, tilted, normal, ta, tb, tc, material),
+            Tri::new(a, c, d, normal, tilted, tilted, normal, ta, tc, td, material)
+        ], true).with_name("quad");
+
+        let mut texture: Vector2D<Color> = Vector2D::new(2, 2, Color::black());
+        texture.data = vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0), Color::new(0.0, 0.0, 1.0), Color::white()];
+        Scene::new(vec![quad], vec![texture], Color::black(), None)
+    }
+
+    #[test]
+    fn exported_scene_loads_back_the_same() {
+        let directory: std::path::PathBuf = std::env::temp_dir().join(format!("r_tracer_obj_export_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path: String = directory.join("quad.obj").to_string_lossy().into_owned();
+        let options: ImportOptions = ImportOptions::y_up().with_scale(0.5).with_flip_v(true);
+        let scene: Scene = scene();
+
+        export_obj(&scene, &path, &options).unwrap();
+        let mut maps: Vec<Vector2D<Color>> = vec![];
+        let tris: Vec<Tri> = load_model_with_materials(&path, &mut maps, Material::empty(), &options).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let expected: &Vec<Tri> = &scene.meshes[0].tris;
+        assert_eq!(tris.len(), expected.len());
+        for (tri, expected) in tris.iter().zip(expected) {
+            for (point, expected_point) in [(tri.p1, expected.p1), (tri.p2, expected.p2), (tri.p3, expected.p3),
+                (tri.p1_normal, expected.p1_normal), (tri.p2_normal, expected.p2_normal), (tri.p3_normal, expected.p3_normal),
+                (tri.normal, expected.normal)] {
+                assert!(close_vector(point, expected_point));
+            }
+            for (uv, expected_uv) in [(tri.p1_texture, expected.p1_texture), (tri.p2_texture, expected.p2_texture), (tri.p3_texture, expected.p3_texture)] {
+                assert!(close(uv.x, expected_uv.x) && close(uv.y, expected_uv.y));
+            }
+        }
+
+        let (material, expected): (Material, Material) = (tris[0].material, expected[0].material);
+        for (color, expected_color) in [(material.diffuse_color, expected.diffuse_color), (material.specular_color, expected.specular_color),
+            (material.emission_color, expected.emission_color), (material.dielectric_color, expected.dielectric_color)] {
+            assert!(close_color(color, expected_color));
+        }
+        for (value, expected_value) in [(material.specular, expected.specular), (material.smoothness, expected.smoothness),
+            (material.dielectric, expected.dielectric), (material.index_of_refraction, expected.index_of_refraction),
+            (material.normal_strength, expected.normal_strength), (material.emission_strength, expected.emission_strength)] {
+            assert!(close(value, expected_value), "{} != {}", value, expected_value);
+        }
+        assert_eq!((material.diffuse_color_map_index, material.normal_map_index), (Some(0), Some(0)));
+        assert_eq!(maps.len(), 1);
+        for (color, expected_color) in maps[0].data.iter().zip(&scene.texture_maps[0].data) {
+            assert!(close_color(*color, *expected_color));
+        }
+    }
+
+    #[test]
+    fn specular_color_is_untinted_for_the_mtl() {
+        let diffuse_color: Color = Color::new(0.5, 0.5, 0.1);
+        let material: Material = Material::builder()
+            .with_diffuse_color(diffuse_color)
+            .with_specular_color(Color::lerp(Color::new(1.0, 0.2, 0.4), diffuse_color, 0.5))
+            .with_specular(0.5)
+            .build(0)
+            .unwrap();
+        assert!(close_color(specular_for_mtl(&material), Color::new(1.0, 0.2, 0.4)));
+
+        // Fully metallic colors have no untinted form and are kept
+        let metal: Material = Material { specular: 1.0, ..material };
+        assert!(close_color(specular_for_mtl(&metal), metal.specular_color));
+    }
+}
@@ -2,10 +2,30 @@ use crate::datatypes::vector2d::Vector2D;
 use crate::datatypes::color::Color;
 
 
+// Post-processing applied to a frame before it is written out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostProcessing {
+    None,
+    RemoveFireflies
+}
+
+impl PostProcessing {
+    pub fn apply(self, color_matrix: &Vector2D<Color>) -> Vector2D<Color> {
+        match self {
+            PostProcessing::None => color_matrix.clone(),
+            PostProcessing::RemoveFireflies => remove_fireflies(color_matrix)
+        }
+    }
+}
+
 pub fn remove_fireflies(color_matrix: &Vector2D<Color>) -> Vector2D<Color> {
+    // Only pixels with all eight neighbours are filtered, thin crops have none and would underflow the loop bounds
+    if color_matrix.width < 3 || color_matrix.height < 3 {
+        return color_matrix.clone()
+    }
     let mut new_colors: Vector2D<Color> = Vector2D::new(color_matrix.width, color_matrix.height, Color::black());
-    let idx_m: [i8; 8] = [-1, -1, -1, 0, 0, 1, 1, 1];
-    let idx_n: [i8; 8] = [-1, 0, 1, -1, 1, -1, 0, 1];
+    let idx_m: [isize; 8] = [-1, -1, -1, 0, 0, 1, 1, 1];
+    let idx_n: [isize; 8] = [-1, 0, 1, -1, 1, -1, 0, 1];
 
     for i in 1..color_matrix.height - 1 {
         for j in 1..color_matrix.width - 1 {
@@ -16,7 +36,7 @@ pub fn remove_fireflies(color_matrix: &Vector2D<Color>) -> Vector2D<Color> {
             let mut color_sum = Color::black();
 
             for n in 0..8 {
-                let neighbor_color: Color = *color_matrix.get(i + idx_m[n] as usize, j + idx_n[n] as usize);
+                let neighbor_color: Color = *color_matrix.get(i.wrapping_add_signed(idx_m[n]), j.wrapping_add_signed(idx_n[n]));
                 if (current_color.to_vector3() - neighbor_color.to_vector3()).magnitude() < 0.2 {
                     similar_found = true;
                     break;
@@ -37,3 +57,26 @@ pub fn remove_fireflies(color_matrix: &Vector2D<Color>) -> Vector2D<Color> {
 
     new_colors
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thin_images_pass_through() {
+        for (width, height) in [(0, 0), (1, 1), (1, 5), (5, 1), (2, 4), (4, 2)] {
+            let mut image: Vector2D<Color> = Vector2D::new(width, height, Color::black());
+            for (i, color) in image.data.iter_mut().enumerate() {
+                *color = Color::new(i as f64, 0.5, 0.25);
+            }
+            assert!(PostProcessing::RemoveFireflies.apply(&image).data == image.data, "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn lone_bright_pixels_are_averaged_away() {
+        let mut image: Vector2D<Color> = Vector2D::new(3, 3, Color::new(0.5, 0.5, 0.5));
+        image.set(1, 1, Color::new(20.0, 20.0, 20.0));
+        assert!(*remove_fireflies(&image).get(1, 1) == Color::new(0.5, 0.5, 0.5));
+    }
+}
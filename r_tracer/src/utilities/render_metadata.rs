@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Duration;
+use exr::prelude::{AttributeValue, LayerAttributes, MetaData, Text};
+use crate::datatypes::vector3::Vector3;
+use crate::spacial::camera::Camera;
+use crate::spacial::camera_basis::CameraBasis;
+use crate::error::RTracerError;
+
+
+const PREFIX: &str = "rtracer:";
+
+// How an image was made, stored as PNG tEXt chunks and EXR header attributes named `rtracer:<field>`
+#[derive(Clone, PartialEq)]
+pub struct RenderMetadata {
+    pub sample_count: u32,
+    pub rays_per_pixel: u32,
+    pub max_bounces: u32,
+    pub camera_position: Vector3,
+    // Axes of the basis the camera rendered with, which is set by either the Euler rotation or a look-at
+    pub camera_forward: Vector3,
+    pub camera_up: Vector3,
+    pub render_time: Duration,
+    pub crate_version: String,
+    pub seed: u64,
+    // Hash of the scene file the camera was loaded from, None for scenes built in code
+    pub scene_hash: Option<String>
+}
+
+impl RenderMetadata {
+    pub fn new(camera: &Camera, sample_count: u32, render_time: Duration) -> RenderMetadata {
+        let basis: CameraBasis = camera.basis();
+        RenderMetadata {
+            sample_count,
+            rays_per_pixel: camera.rays_per_pixel,
+            max_bounces: camera.max_bounces,
+            camera_position: camera.position,
+            camera_forward: basis.forward,
+            camera_up: basis.up,
+            render_time,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: camera.seed,
+            scene_hash: camera.scene.file_hash.map(|hash: u64| format!("{:016x}", hash))
+        }
+    }
+
+    pub fn to_pairs(&self) -> Vec<(String, String)> {
+        let vector = |v: Vector3| -> String { format!("{} {} {}", v.x, v.y, v.z) };
+        let mut pairs: Vec<(&str, String)> = vec![
+            ("sample_count", self.sample_count.to_string()),
+            ("rays_per_pixel", self.rays_per_pixel.to_string()),
+            ("max_bounces", self.max_bounces.to_string()),
+            ("camera_position", vector(self.camera_position)),
+            ("camera_forward", vector(self.camera_forward)),
+            ("camera_up", vector(self.camera_up)),
+            ("render_time", self.render_time.as_secs_f64().to_string()),
+            ("crate_version", self.crate_version.clone()),
+            ("seed", self.seed.to_string())
+        ];
+        if let Some(scene_hash) = &self.scene_hash {
+            pairs.push(("scene_hash", scene_hash.clone()));
+        }
+        pairs.into_iter().map(|(key, value)| (format!("{}{}", PREFIX, key), value)).collect()
+    }
+
+    // Pairs without the prefix are skipped, so other chunks and attributes in the file do not get in the way
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<RenderMetadata, String> {
+        let get = |key: &str| -> Result<&str, String> {
+            pairs.iter().find(|(name, _)| name.strip_prefix(PREFIX) == Some(key))
+                .map(|(_, value)| value.as_str())
+                .ok_or_else(|| format!("missing {}{}", PREFIX, key))
+        };
+        let number = |key: &str| -> Result<u64, String> {
+            get(key)?.trim().parse().map_err(|_| format!("{}{} is not a whole number", PREFIX, key))
+        };
+        let vector = |key: &str| -> Result<Vector3, String> {
+            let values: Vec<f64> = get(key)?.split_whitespace().filter_map(|value: &str| value.parse().ok()).collect();
+            match values[..] {
+                [x, y, z] => Ok(Vector3::new(x, y, z)),
+                _ => Err(format!("{}{} is not three numbers", PREFIX, key))
+            }
+        };
+        let seconds = || -> Result<f64, String> {
+            get("render_time")?.trim().parse().map_err(|_| format!("{}render_time is not a number", PREFIX))
+        };
+
+        Ok(RenderMetadata {
+            sample_count: number("sample_count")? as u32,
+            rays_per_pixel: number("rays_per_pixel")? as u32,
+            max_bounces: number("max_bounces")? as u32,
+            camera_position: vector("camera_position")?,
+            camera_forward: vector("camera_forward")?,
+            camera_up: vector("camera_up")?,
+            render_time: Duration::try_from_secs_f64(seconds()?).map_err(|err| err.to_string())?,
+            crate_version: get("crate_version")?.to_string(),
+            seed: number("seed")?,
+            scene_hash: get("scene_hash").ok().map(str::to_string)
+        })
+    }
+
+    pub(crate) fn to_exr_attributes(&self) -> LayerAttributes {
+        let mut attributes: LayerAttributes = LayerAttributes {
+            software_name: Text::new_or_none(format!("RTracer {}", self.crate_version)),
+            ..LayerAttributes::default()
+        };
+        for (key, value) in self.to_pairs() {
+            if let Some(text) = Text::new_or_none(&value) {
+                attributes.other.insert(Text::new_or_panic(key), AttributeValue::Text(text));
+            }
+        }
+        attributes
+    }
+
+    // Reads the metadata back out of a .png or .exr written with it
+    pub fn read(path: &str) -> Result<RenderMetadata, RTracerError> {
+        let extension: String = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        let pairs: Vec<(String, String)> = match extension.as_str() {
+            "png" => read_png_text(path)?,
+            "exr" => read_exr_text(path)?,
+            _ => return Err(RTracerError::UnsupportedFormat { path: path.to_string() })
+        };
+        RenderMetadata::from_pairs(&pairs).map_err(|message: String| RTracerError::Metadata { path: path.to_string(), message })
+    }
+}
+
+fn read_png_text(path: &str) -> Result<Vec<(String, String)>, RTracerError> {
+    let file: File = File::open(path).map_err(|err| RTracerError::io(path, err))?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info()
+        .map_err(|err| RTracerError::Metadata { path: path.to_string(), message: err.to_string() })?;
+    Ok(reader.info().uncompressed_latin1_text.iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+        .collect())
+}
+
+fn read_exr_text(path: &str) -> Result<Vec<(String, String)>, RTracerError> {
+    let meta_data: MetaData = MetaData::read_from_file(path, false)
+        .map_err(|err: exr::error::Error| RTracerError::Metadata { path: path.to_string(), message: err.to_string() })?;
+    Ok(meta_data.headers.iter()
+        .flat_map(|header| &header.own_attributes.other)
+        .filter_map(|(name, value): (&Text, &AttributeValue)| match value {
+            AttributeValue::Text(text) => Some((name.to_string(), text.to_string())),
+            _ => None
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::color::Color;
+    use crate::datatypes::vector2d::Vector2D;
+    use crate::utilities::file_utilities::{save_vector2d_as_exr_with_metadata, save_vector2d_as_png_with_metadata, ExrPrecision};
+    use crate::utilities::postprocessing::PostProcessing;
+
+    fn metadata(scene_hash: Option<String>) -> RenderMetadata {
+        RenderMetadata {
+            sample_count: 64,
+            rays_per_pixel: 4,
+            max_bounces: 6,
+            camera_position: Vector3::new(-3.5, 0.25, 1.0),
+            camera_forward: Vector3::new(0.6, 0.0, -0.8),
+            camera_up: Vector3::new(0.8, 0.0, 0.6),
+            render_time: Duration::from_millis(12345),
+            crate_version: String::from("9.8.7"),
+            seed: u64::MAX - 1,
+            scene_hash
+        }
+    }
+
+    fn round_trip(extension: &str, metadata: &RenderMetadata) -> RenderMetadata {
+        let path: String = std::env::temp_dir()
+            .join(format!("r_tracer_metadata_{}.{}", std::process::id(), extension))
+            .to_string_lossy().into_owned();
+        let image: Vector2D<Color> = Vector2D::new(3, 2, Color::new(0.5, 0.25, 1.0));
+        match extension {
+            "png" => save_vector2d_as_png_with_metadata(&image, &path, PostProcessing::None, metadata),
+            _ => save_vector2d_as_exr_with_metadata(&image, &path, ExrPrecision::Half, PostProcessing::None, metadata)
+        }.unwrap();
+        let read: Result<RenderMetadata, RTracerError> = RenderMetadata::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        read.unwrap()
+    }
+
+    #[test]
+    fn png_metadata_reads_back() {
+        let written: RenderMetadata = metadata(Some(String::from("00ff00ff00ff00ff")));
+        assert!(round_trip("png", &written) == written);
+    }
+
+    #[test]
+    fn exr_metadata_reads_back() {
+        let written: RenderMetadata = metadata(Some(String::from("00ff00ff00ff00ff")));
+        assert!(round_trip("exr", &written) == written);
+    }
+
+    #[test]
+    fn scenes_built_in_code_have_no_hash() {
+        let written: RenderMetadata = metadata(None);
+        for extension in ["png", "exr"] {
+            assert!(round_trip(extension, &written).scene_hash.is_none());
+        }
+    }
+
+    #[test]
+    fn pairs_without_the_prefix_are_skipped() {
+        let written: RenderMetadata = metadata(None);
+        let mut pairs: Vec<(String, String)> = vec![
+            (String::from("seed"), String::from("not a number")),
+            (String::from("Software"), String::from("Something else")),
+            (String::from("other:scene_hash"), String::from("0123456789abcdef"))
+        ];
+        pairs.extend(written.to_pairs());
+        assert!(RenderMetadata::from_pairs(&pairs).unwrap() == written);
+
+        // Without its own pairs there is nothing to read
+        assert_eq!(RenderMetadata::from_pairs(&pairs[..3]).err().unwrap(), "missing rtracer:sample_count");
+    }
+}
@@ -0,0 +1,28 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+
+thread_local! {
+    static SAMPLER: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Restarts this thread's random sequence for one sample of one pixel. Every sample of every pixel gets its
+// own stream, so a render draws the same numbers no matter how it is split across threads, tiles or runs.
+pub fn seed_pixel(seed: u64, sample: u64, pixel: usize) {
+    let mut key: [u8; 32] = [0; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..16].copy_from_slice(&sample.to_le_bytes());
+    key[16..24].copy_from_slice(&(pixel as u64).to_le_bytes());
+    SAMPLER.with(|sampler: &RefCell<StdRng>| *sampler.borrow_mut() = StdRng::from_seed(key));
+}
+
+// Uniform in [0, 1)
+pub fn random() -> f64 {
+    SAMPLER.with(|sampler: &RefCell<StdRng>| sampler.borrow_mut().gen())
+}
+
+pub fn random_range(range: Range<f64>) -> f64 {
+    SAMPLER.with(|sampler: &RefCell<StdRng>| sampler.borrow_mut().gen_range(range))
+}
@@ -0,0 +1,701 @@
+use serde::Deserialize;
+use toml::Spanned;
+use crate::spacial::scene::Scene;
+use crate::spacial::camera::{Camera, FisheyeMapping};
+use crate::spacial::camera_builder::CameraBuilder;
+use crate::spacial::physical_camera::PhysicalCamera;
+use crate::spacial::aperture::Aperture;
+use crate::spacial::crop_region::CropRegion;
+use crate::spacial::mesh_object::MeshObject;
+use crate::spacial::tri::Tri;
+use crate::datatypes::material::{Material, MaterialBuilder};
+use crate::datatypes::color::Color;
+use crate::datatypes::vector3::Vector3;
+use crate::datatypes::vector2d::Vector2D;
+use crate::utilities::file_utilities::{load_model, load_model_with_materials, import_texture};
+use crate::utilities::import_options::{ImportOptions, UpAxis, Handedness};
+use crate::utilities::mesh_cache::{MeshCache, fnv1a, FNV_OFFSET};
+use crate::error::RTracerError;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+
+#[derive(Debug, Clone)]
+pub struct SceneFileError {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String
+}
+
+impl SceneFileError {
+    fn new(path: &str, line: Option<usize>, message: String) -> SceneFileError {
+        SceneFileError { path: path.to_string(), line, message }
+    }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+            None => write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: Option<CameraDescription>,
+    #[serde(default)]
+    pub environment: EnvironmentDescription,
+    #[serde(default)]
+    pub textures: HashMap<String, Spanned<String>>,
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub meshes: Vec<MeshDescription>,
+    // Directory for the mesh and BVH cache, created on first use
+    pub cache: Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: [f64; 3],
+    #[serde(default)]
+    pub rotation: [f64; 3],
+    // Point to look at, used instead of `rotation` when given
+    pub target: Option<[f64; 3]>,
+    #[serde(default = "default_up")]
+    pub up: [f64; 3],
+    #[serde(default = "default_exposure")]
+    pub exposure: f64,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default = "default_max_bounces")]
+    pub max_bounces: u32,
+    #[serde(default = "default_rays_per_pixel")]
+    pub rays_per_pixel: u32,
+    #[serde(default)]
+    pub blur_strength: f64,
+    #[serde(default)]
+    pub dof_strength: f64,
+    #[serde(default = "default_focal_distance")]
+    pub focal_distance: f64,
+    #[serde(default = "default_fov")]
+    pub fov: f64,
+    // Either of these, in degrees, is used instead of `fov`
+    pub horizontal_fov: Option<f64>,
+    pub vertical_fov: Option<f64>,
+    // Defaults to orthographic when `orthographic_scale` is given and perspective otherwise
+    pub projection: Option<ProjectionDescription>,
+    // Width of the orthographic view in world units, across the wider side of the image
+    pub orthographic_scale: Option<f64>,
+    #[serde(default = "default_fisheye_fov")]
+    pub fisheye_fov: f64,
+    // [camera.physical], real camera settings that replace fov and dof_strength
+    pub physical: Option<PhysicalDescription>,
+    // [camera.aperture], a disk unless it gives blades or an image
+    pub aperture: Option<ApertureDescription>,
+    // Times between 0 and 1 the shutter is open for, motion blur needs shutter_close above shutter_open
+    #[serde(default)]
+    pub shutter_open: f64,
+    #[serde(default)]
+    pub shutter_close: f64,
+    // [camera.motion], where the camera is at the end of the frame
+    pub motion: Option<CameraMotionDescription>,
+    // [camera.stereo], eye separation and convergence for Camera::render_stereo
+    pub stereo: Option<StereoDescription>,
+    // [left, top, width, height] in pixels, renders only that part of the frame
+    pub crop: Option<[usize; 4]>,
+    #[serde(default)]
+    pub tile_size: usize,
+    #[serde(default)]
+    pub seed: u64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicalDescription {
+    #[serde(default = "default_sensor_width")]
+    pub sensor_width: f64,
+    #[serde(default = "default_sensor_height")]
+    pub sensor_height: f64,
+    pub focal_length: f64,
+    pub f_number: f64,
+    pub shutter_time: f64,
+    pub iso: f64,
+    #[serde(default = "default_scale")]
+    pub units_per_meter: f64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApertureDescription {
+    pub blades: Option<u32>,
+    #[serde(default)]
+    pub rotation: f64,
+    pub image: Option<Spanned<String>>,
+    #[serde(default = "default_scale")]
+    pub squeeze: f64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraMotionDescription {
+    pub position: [f64; 3],
+    // Defaults to the start rotation, or to looking at the start target for a camera with one
+    pub rotation: Option<[f64; 3]>,
+    pub target: Option<[f64; 3]>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StereoDescription {
+    pub interocular_distance: f64,
+    pub convergence_distance: f64
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionDescription {
+    Perspective,
+    Orthographic,
+    Equirectangular,
+    EquidistantFisheye,
+    EquisolidFisheye
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDescription {
+    #[serde(default)]
+    pub color: [f64; 3],
+    pub map: Option<Spanned<String>>,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+    #[serde(default)]
+    pub rotation: f64
+}
+
+impl Default for EnvironmentDescription {
+    fn default() -> EnvironmentDescription {
+        EnvironmentDescription { color: [0.0; 3], map: None, intensity: default_intensity(), rotation: 0.0 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+    #[serde(default)]
+    pub diffuse_color: [f64; 3],
+    #[serde(default)]
+    pub emission_color: [f64; 3],
+    #[serde(default)]
+    pub specular_color: [f64; 3],
+    #[serde(default)]
+    pub dielectric_color: [f64; 3],
+    #[serde(default)]
+    pub emission_strength: f64,
+    #[serde(default)]
+    pub smoothness: f64,
+    #[serde(default)]
+    pub specular: f64,
+    #[serde(default)]
+    pub dielectric: f64,
+    #[serde(default = "default_index_of_refraction")]
+    pub index_of_refraction: f64,
+    #[serde(default = "default_normal_strength")]
+    pub normal_strength: f64,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default)]
+    pub use_vertex_colors: bool,
+    pub diffuse_color_map: Option<Spanned<String>>,
+    pub emission_color_map: Option<Spanned<String>>,
+    pub specular_color_map: Option<Spanned<String>>,
+    pub dielectric_color_map: Option<Spanned<String>>,
+    pub normal_map: Option<Spanned<String>>,
+    pub smoothness_map: Option<Spanned<String>>,
+    pub specular_map: Option<Spanned<String>>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshDescription {
+    pub file: Spanned<String>,
+    // Defaults to the file name without its extension
+    pub name: Option<String>,
+    pub material: Option<Spanned<String>>,
+    #[serde(default = "default_smooth_shading")]
+    pub smooth_shading: bool,
+    // Axis conventions default to those of the file format
+    pub up_axis: Option<UpAxis>,
+    pub handedness: Option<Handedness>,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub flip_winding: bool,
+    #[serde(default)]
+    pub flip_v: bool,
+    // Where the mesh ends up at the end of the frame
+    pub motion: Option<MeshMotionDescription>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshMotionDescription {
+    #[serde(default)]
+    pub translation: [f64; 3],
+    // Degrees about the center of the mesh
+    #[serde(default)]
+    pub rotation: [f64; 3]
+}
+
+impl MeshDescription {
+    pub fn import_options(&self) -> ImportOptions {
+        let mut options: ImportOptions = ImportOptions::for_file(self.file.get_ref())
+            .with_scale(self.scale)
+            .with_flip_winding(self.flip_winding)
+            .with_flip_v(self.flip_v);
+        if let Some(up_axis) = self.up_axis {
+            options.up_axis = up_axis;
+        }
+        if let Some(handedness) = self.handedness {
+            options.handedness = handedness;
+        }
+        options
+    }
+}
+
+fn default_up() -> [f64; 3] { [0.0, 0.0, 1.0] }
+fn default_exposure() -> f64 { 1.0 }
+fn default_fisheye_fov() -> f64 { 180.0 }
+fn default_sensor_width() -> f64 { 36.0 }
+fn default_sensor_height() -> f64 { 24.0 }
+fn default_max_bounces() -> u32 { 8 }
+fn default_rays_per_pixel() -> u32 { 1 }
+fn default_fov() -> f64 { 1.0 }
+fn default_focal_distance() -> f64 { 1.0 }
+fn default_index_of_refraction() -> f64 { 1.5 }
+fn default_normal_strength() -> f64 { 1.0 }
+fn default_visible() -> bool { true }
+fn default_smooth_shading() -> bool { true }
+fn default_scale() -> f64 { 1.0 }
+fn default_intensity() -> f64 { 1.0 }
+
+
+impl SceneDescription {
+    pub fn parse(source: &str, path: &str) -> Result<SceneDescription, SceneFileError> {
+        toml::from_str(source).map_err(|err: toml::de::Error| {
+            let line: Option<usize> = err.span().map(|span: Range<usize>| line_of(source, span));
+            SceneFileError::new(path, line, err.message().to_string())
+        })
+    }
+}
+
+type SpannedTable = Spanned<HashMap<String, Spanned<toml::Value>>>;
+type MapSetter = fn(MaterialBuilder, usize) -> MaterialBuilder;
+
+// Spans of the tables whose values are checked after parsing, read again to give those errors a line
+#[derive(Deserialize)]
+struct EntrySpans {
+    camera: Option<SpannedTable>,
+    #[serde(default)]
+    materials: HashMap<String, SpannedTable>
+}
+
+// The entry named `key`, in the table or one of its sub-tables, or the table itself when the entry is not
+// in the file
+fn entry_span(table: &SpannedTable, key: &str) -> Range<usize> {
+    let entries: &HashMap<String, Spanned<toml::Value>> = table.get_ref();
+    entries.get(key)
+        .or_else(|| entries.values().find(|entry: &&Spanned<toml::Value>| entry.get_ref().get(key).is_some()))
+        .map_or(table.span(), |entry: &Spanned<toml::Value>| entry.span())
+}
+
+// Builds a Scene (and optionally its Camera) out of a parsed description. Model and texture
+// paths are resolved relative to the directory holding the scene file.
+struct SceneBuilder<'a> {
+    source: &'a str,
+    path: &'a str,
+    base_dir: PathBuf,
+    texture_indices: HashMap<String, usize>,
+    cache: Option<MeshCache>
+}
+
+impl<'a> SceneBuilder<'a> {
+    fn error(&self, span: Range<usize>, message: String) -> SceneFileError {
+        SceneFileError::new(self.path, Some(line_of(self.source, span)), message)
+    }
+
+    fn resolve_path(&self, file: &Spanned<String>) -> Result<String, SceneFileError> {
+        let resolved: PathBuf = self.base_dir.join(file.get_ref());
+        if !resolved.is_file() {
+            return Err(self.error(file.span(), format!("file not found: {}", resolved.display())))
+        }
+        Ok(resolved.to_string_lossy().into_owned())
+    }
+
+    // Asset errors are reported against the scene entry that referenced the asset
+    fn asset_error(&self, span: Range<usize>, err: RTracerError) -> SceneFileError {
+        self.error(span, err.to_string())
+    }
+
+    fn texture_index(&self, name: &Option<Spanned<String>>) -> Result<Option<usize>, SceneFileError> {
+        match name {
+            None => Ok(None),
+            Some(name) => match self.texture_indices.get(name.get_ref()) {
+                Some(&index) => Ok(Some(index)),
+                None => Err(self.error(name.span(), format!("unknown texture '{}'", name.get_ref())))
+            }
+        }
+    }
+
+    // Goes through MaterialBuilder so scene file materials are checked the same way as ones built in code
+    fn build_material(&self, name: &str, desc: &MaterialDescription) -> Result<Material, SceneFileError> {
+        let mut builder: MaterialBuilder = Material::builder()
+            .with_diffuse_color(to_color(desc.diffuse_color))
+            .with_emission(to_color(desc.emission_color), desc.emission_strength)
+            .with_specular_color(to_color(desc.specular_color))
+            .with_dielectric_color(to_color(desc.dielectric_color))
+            .with_smoothness(desc.smoothness)
+            .with_specular(desc.specular)
+            .with_dielectric(desc.dielectric)
+            .with_index_of_refraction(desc.index_of_refraction)
+            .with_normal_strength(desc.normal_strength)
+            .with_visible(desc.visible)
+            .with_vertex_colors(desc.use_vertex_colors);
+        let maps: [(&Option<Spanned<String>>, MapSetter); 7] = [
+            (&desc.diffuse_color_map, MaterialBuilder::with_diffuse_color_map),
+            (&desc.emission_color_map, MaterialBuilder::with_emission_color_map),
+            (&desc.specular_color_map, MaterialBuilder::with_specular_color_map),
+            (&desc.dielectric_color_map, MaterialBuilder::with_dielectric_color_map),
+            (&desc.normal_map, MaterialBuilder::with_normal_map),
+            (&desc.smoothness_map, MaterialBuilder::with_smoothness_map),
+            (&desc.specular_map, MaterialBuilder::with_specular_map)
+        ];
+        for (map, with_map) in maps {
+            if let Some(index) = self.texture_index(map)? {
+                builder = with_map(builder, index);
+            }
+        }
+        builder.build(self.texture_indices.len()).map_err(|err: RTracerError| {
+            let key: &str = match &err {
+                RTracerError::InvalidParameter { name, .. } => name,
+                _ => ""
+            };
+            let span: Option<Range<usize>> = self.entry_spans().and_then(|spans: EntrySpans| {
+                spans.materials.get(name).map(|table: &SpannedTable| entry_span(table, key))
+            });
+            SceneFileError::new(self.path, span.map(|span: Range<usize>| line_of(self.source, span)),
+                format!("[materials.{}]: {}", name, err))
+        })
+    }
+
+    fn load_model(&self, path: &str, material: Material, options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+        match &self.cache {
+            Some(cache) => cache.load_model(path, material, options),
+            None => load_model(path, material, options)
+        }
+    }
+
+    fn load_model_with_materials(&self, path: &str, maps: &mut Vec<Vector2D<Color>>,
+        options: &ImportOptions) -> Result<Vec<Tri>, RTracerError> {
+        match &self.cache {
+            Some(cache) => cache.load_model_with_materials(path, maps, default_material(), options),
+            None => load_model_with_materials(path, maps, default_material(), options)
+        }
+    }
+
+    // Goes through CameraBuilder so scene file cameras are checked the same way as ones built in code
+    fn build_camera(&self, desc: &CameraDescription, scene: Scene) -> Result<Camera, SceneFileError> {
+        let mut builder: CameraBuilder = Camera::builder(scene, desc.width, desc.height)
+            .with_position(to_vector3(desc.position))
+            .with_rotation(to_vector3(desc.rotation))
+            .with_exposure(desc.exposure)
+            .with_max_bounces(desc.max_bounces)
+            .with_rays_per_pixel(desc.rays_per_pixel)
+            .with_blur_strength(desc.blur_strength)
+            .with_depth_of_field(desc.dof_strength, desc.focal_distance)
+            .with_fov(desc.fov)
+            .with_shutter(desc.shutter_open, desc.shutter_close)
+            .with_tile_size(desc.tile_size)
+            .with_seed(desc.seed);
+
+        builder = match (desc.horizontal_fov, desc.vertical_fov) {
+            (Some(_), Some(_)) => return Err(self.camera_error("vertical_fov", "give either horizontal_fov or vertical_fov, not both".to_string())),
+            (Some(degrees), None) => builder.with_horizontal_fov(degrees),
+            (None, Some(degrees)) => builder.with_vertical_fov(degrees),
+            (None, None) => builder
+        };
+        let projection: ProjectionDescription = desc.projection.unwrap_or(match desc.orthographic_scale {
+            Some(_) => ProjectionDescription::Orthographic,
+            None => ProjectionDescription::Perspective
+        });
+        builder = match (projection, desc.orthographic_scale) {
+            (ProjectionDescription::Orthographic, Some(scale)) => builder.with_orthographic(scale),
+            (ProjectionDescription::Orthographic, None) => return Err(self.camera_error("projection", "the orthographic projection needs orthographic_scale".to_string())),
+            (_, Some(_)) => return Err(self.camera_error("orthographic_scale", "orthographic_scale only applies to the orthographic projection".to_string())),
+            (ProjectionDescription::Perspective, None) => builder,
+            (ProjectionDescription::Equirectangular, None) => builder.with_equirectangular(),
+            (ProjectionDescription::EquidistantFisheye, None) => builder.with_fisheye(FisheyeMapping::Equidistant, desc.fisheye_fov),
+            (ProjectionDescription::EquisolidFisheye, None) => builder.with_fisheye(FisheyeMapping::Equisolid, desc.fisheye_fov)
+        };
+        if let Some(physical_desc) = &desc.physical {
+            builder = builder.with_physical(PhysicalCamera::new(
+                physical_desc.focal_length, physical_desc.f_number, physical_desc.shutter_time, physical_desc.iso
+            ).with_sensor(physical_desc.sensor_width, physical_desc.sensor_height)
+                .with_units_per_meter(physical_desc.units_per_meter));
+        }
+        if let Some(aperture_desc) = &desc.aperture {
+            builder = builder.with_aperture(self.build_aperture(aperture_desc)?);
+        }
+        if let Some(target) = desc.target {
+            builder = builder.with_look_at(to_vector3(desc.position), to_vector3(target), to_vector3(desc.up));
+        }
+        if let Some(motion_desc) = &desc.motion {
+            let position: Vector3 = to_vector3(motion_desc.position);
+            builder = match (motion_desc.rotation, motion_desc.target) {
+                (Some(_), Some(_)) => return Err(self.camera_error("motion", "give the motion either a rotation or a target, not both".to_string())),
+                (Some(rotation), None) => builder.with_motion(position, to_vector3(rotation)),
+                (None, Some(target)) => builder.with_motion_look_at(position, to_vector3(target), to_vector3(desc.up)),
+                // Keeps the start rotation, or keeps looking at the start target
+                (None, None) => match desc.target {
+                    Some(target) => builder.with_motion_look_at(position, to_vector3(target), to_vector3(desc.up)),
+                    None => builder.with_motion(position, to_vector3(desc.rotation))
+                }
+            };
+        }
+        if let Some(stereo_desc) = &desc.stereo {
+            builder = builder.with_stereo(stereo_desc.interocular_distance, stereo_desc.convergence_distance);
+        }
+        if let Some([left, top, width, height]) = desc.crop {
+            builder = builder.with_crop(CropRegion::new(left, top, width, height));
+        }
+        builder.build().map_err(|err: RTracerError| self.camera_parameter_error(err))
+    }
+
+    fn entry_spans(&self) -> Option<EntrySpans> {
+        toml::from_str(self.source).ok()
+    }
+
+    fn camera_error(&self, key: &str, message: String) -> SceneFileError {
+        let span: Option<Range<usize>> = self.entry_spans()
+            .and_then(|spans: EntrySpans| spans.camera.map(|table: SpannedTable| entry_span(&table, key)));
+        SceneFileError::new(self.path, span.map(|span: Range<usize>| line_of(self.source, span)), format!("[camera]: {}", message))
+    }
+
+    // Invalid parameters are named after the builder setting, which mostly matches the scene file entry
+    fn camera_parameter_error(&self, err: RTracerError) -> SceneFileError {
+        let key: &str = match &err {
+            RTracerError::InvalidParameter { name, .. } => match name.as_str() {
+                "resolution" => "width",
+                "look_at" | "look_at target" => "target",
+                "look_at up" => "up",
+                // "motion eye", "aperture blades", "crop region" and the like
+                name => name.split(' ').next().unwrap_or(name)
+            },
+            _ => ""
+        };
+        self.camera_error(key, err.to_string())
+    }
+
+    fn build_aperture(&self, desc: &ApertureDescription) -> Result<Aperture, SceneFileError> {
+        let aperture: Aperture = match (&desc.image, desc.blades) {
+            (Some(image), None) => Aperture::from_file(&self.resolve_path(image)?)
+                .map_err(|err: RTracerError| self.asset_error(image.span(), err))?,
+            (None, Some(blades)) => Aperture::polygon(blades, desc.rotation),
+            (None, None) => Aperture::disk(),
+            (Some(image), Some(_)) => return Err(self.error(image.span(), "give either an aperture image or blades, not both".to_string()))
+        };
+        Ok(aperture.with_squeeze(desc.squeeze))
+    }
+
+    fn build_scene(&mut self, desc: &SceneDescription) -> Result<Scene, SceneFileError> {
+        // Sort by name so texture indices are stable between runs
+        let mut texture_names: Vec<&String> = desc.textures.keys().collect();
+        texture_names.sort();
+
+        let mut maps: Vec<Vector2D<Color>> = vec![];
+        for name in texture_names {
+            let texture: &Spanned<String> = &desc.textures[name];
+            let texture_path: String = self.resolve_path(texture)?;
+            self.texture_indices.insert(name.clone(), maps.len());
+            maps.push(import_texture(&texture_path).map_err(|err| self.asset_error(texture.span(), err))?);
+        }
+
+        let mut materials: HashMap<&String, Material> = HashMap::new();
+        for (name, material_desc) in &desc.materials {
+            materials.insert(name, self.build_material(name, material_desc)?);
+        }
+
+        let mut meshes: Vec<MeshObject> = vec![];
+        for mesh_desc in &desc.meshes {
+            let model_path: String = self.resolve_path(&mesh_desc.file)?;
+            let options: ImportOptions = mesh_desc.import_options();
+            let tris: Result<Vec<Tri>, RTracerError> = match &mesh_desc.material {
+                Some(name) => match materials.get(name.get_ref()) {
+                    Some(&material) => self.load_model(&model_path, material, &options),
+                    None => return Err(self.error(name.span(), format!("unknown material '{}'", name.get_ref())))
+                },
+                // Without an explicit material the model's own material library is used
+                None => self.load_model_with_materials(&model_path, &mut maps, &options)
+            };
+            let tris: Vec<Tri> = tris.map_err(|err| self.asset_error(mesh_desc.file.span(), err))?;
+            let name: String = mesh_desc.name.clone().unwrap_or_else(|| {
+                Path::new(mesh_desc.file.get_ref()).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+            });
+            let mut mesh: MeshObject = MeshObject::new(tris, mesh_desc.smooth_shading).with_name(&name);
+            if let Some(motion) = &mesh_desc.motion {
+                mesh = mesh.with_motion(to_vector3(motion.translation), to_vector3(motion.rotation));
+            }
+            meshes.push(mesh);
+        }
+
+        let environment_map: Option<Vector2D<Color>> = match &desc.environment.map {
+            Some(map) => Some(import_texture(&self.resolve_path(map)?).map_err(|err| self.asset_error(map.span(), err))?),
+            None => None
+        };
+
+        Ok(Scene::new(meshes, maps, to_color(desc.environment.color), environment_map)
+            .with_environment_intensity(desc.environment.intensity)
+            .with_environment_rotation(desc.environment.rotation))
+    }
+}
+
+
+fn read_description(path: &str) -> Result<(String, SceneDescription), SceneFileError> {
+    let source: String = fs::read_to_string(path)
+        .map_err(|err| SceneFileError::new(path, None, err.to_string()))?;
+    let desc: SceneDescription = SceneDescription::parse(&source, path)?;
+    Ok((source, desc))
+}
+
+fn new_builder<'a>(source: &'a str, path: &'a str, desc: &SceneDescription) -> SceneBuilder<'a> {
+    let base_dir: PathBuf = Path::new(path).parent().map(Path::to_path_buf).unwrap_or_default();
+    SceneBuilder {
+        source,
+        path,
+        cache: desc.cache.as_ref().map(|cache: &String| MeshCache::new(&base_dir.join(cache).to_string_lossy())),
+        base_dir,
+        texture_indices: HashMap::new()
+    }
+}
+
+pub fn load_scene(path: &str) -> Result<Scene, SceneFileError> {
+    let (source, desc) = read_description(path)?;
+    let scene: Scene = new_builder(&source, path, &desc).build_scene(&desc)?;
+    Ok(scene.with_file_hash(fnv1a(FNV_OFFSET, source.as_bytes())))
+}
+
+pub fn load_scene_with_camera(path: &str) -> Result<Camera, SceneFileError> {
+    let (source, desc) = read_description(path)?;
+    let camera_desc: &CameraDescription = desc.camera.as_ref()
+        .ok_or_else(|| SceneFileError::new(path, None, "missing [camera] section".to_string()))?;
+    let mut builder: SceneBuilder = new_builder(&source, path, &desc);
+    let scene: Scene = builder.build_scene(&desc)?.with_file_hash(fnv1a(FNV_OFFSET, source.as_bytes()));
+    let camera: Camera = builder.build_camera(camera_desc, scene)?;
+    Ok(match builder.cache {
+        Some(cache) => camera.with_cache(cache),
+        None => camera
+    })
+}
+
+fn line_of(source: &str, span: Range<usize>) -> usize {
+    let end: usize = usize::min(span.start, source.len());
+    source[..end].matches('\n').count() + 1
+}
+
+// The builder's defaults, which always pass its checks
+fn default_material() -> Material {
+    Material::builder().build(0).unwrap_or_else(|_| Material::empty())
+}
+
+fn to_color(values: [f64; 3]) -> Color {
+    Color::new(values[0], values[1], values[2])
+}
+
+fn to_vector3(values: [f64; 3]) -> Vector3 {
+    Vector3::new(values[0], values[1], values[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `source` to a scene file of its own and loads it with its camera
+    fn load_camera(name: &str, source: &str) -> Result<Camera, SceneFileError> {
+        let path: PathBuf = std::env::temp_dir().join(format!("r_tracer_{}_{}.toml", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        let camera: Result<Camera, SceneFileError> = load_scene_with_camera(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        camera
+    }
+
+    fn load_error(name: &str, source: &str) -> SceneFileError {
+        match load_camera(name, source) {
+            Ok(_) => panic!("{} loaded without an error", name),
+            Err(err) => err
+        }
+    }
+
+    #[test]
+    fn loads_a_valid_camera() {
+        let camera: Camera = load_camera("valid_camera", "[camera]\nposition = [1.0, 2.0, 3.0]\nwidth = 8\nheight = 4\nvertical_fov = 50.0\n").unwrap();
+        assert_eq!((camera.width, camera.height), (8, 4));
+        assert!(camera.position == Vector3::new(1.0, 2.0, 3.0));
+        assert!(camera.scene.environment_map.is_none());
+    }
+
+    #[test]
+    fn camera_errors_point_at_their_entry() {
+        let header: &str = "# camera\n[camera]\nposition = [0.0, 0.0, 0.0]\n";
+        let cases: [(&str, &str, usize); 6] = [
+            ("zero_width", "width = 0\nheight = 4\n", 4),
+            ("zero_rays", "width = 4\nheight = 4\nrays_per_pixel = 0\n", 6),
+            ("infinite_fov", "width = 4\nheight = 4\nfov = inf\n", 6),
+            ("wide_horizontal_fov", "width = 4\nheight = 4\nhorizontal_fov = 180.0\n", 6),
+            ("bad_f_number", "width = 4\nheight = 4\n\n[camera.physical]\nfocal_length = 50.0\nf_number = -2.0\nshutter_time = 0.01\niso = 100.0\n", 7),
+            ("crop_outside", "width = 4\nheight = 4\ncrop = [2, 0, 4, 4]\n", 6)
+        ];
+        for (name, camera, line) in cases {
+            let err: SceneFileError = load_error(name, &format!("{}{}", header, camera));
+            assert_eq!(err.line, Some(line), "{}: {}", name, err);
+            assert!(err.message.starts_with("[camera]: "), "{}: {}", name, err);
+        }
+    }
+
+    #[test]
+    fn loads_scene_file_materials() {
+        let source: &str = "[materials.glass]\ndiffuse_color = [0.5, 0.5, 0.5]\ndielectric = 1.0\nindex_of_refraction = 1.33\nsmoothness = 0.9\n";
+        let desc: SceneDescription = SceneDescription::parse(source, "glass.toml").unwrap();
+        let material: Material = new_builder(source, "glass.toml", &desc).build_material("glass", &desc.materials["glass"]).unwrap();
+        assert!(material.diffuse_color == Color::new(0.5, 0.5, 0.5));
+        assert_eq!((material.dielectric, material.index_of_refraction, material.smoothness), (1.0, 1.33, 0.9));
+        assert!(material.visible);
+    }
+
+    #[test]
+    fn rejects_invalid_materials() {
+        let cases: [(&str, &str); 5] = [
+            ("index_of_refraction = 0.0", "index_of_refraction"),
+            ("smoothness = 1.5", "smoothness"),
+            ("specular = -0.1", "specular"),
+            ("emission_strength = nan", "emission_strength"),
+            ("diffuse_color = [0.5, -1.0, 0.5]", "diffuse_color")
+        ];
+        for (entry, name) in cases {
+            let source: String = format!("[camera]\nposition = [0.0, 0.0, 0.0]\nwidth = 4\nheight = 4\n\n[materials.bad]\nvisible = true\n{}\n", entry);
+            let err: SceneFileError = load_error(name, &source);
+            assert_eq!(err.line, Some(8), "{}", err);
+            assert!(err.message.starts_with(&format!("[materials.bad]: invalid {}", name)), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::datatypes::vector3::Vector3;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+
+#[derive(Copy, Clone)]
+pub struct WeldOptions {
+    pub weld_tolerance: f64,
+    pub crease_angle: f64
+}
+
+impl WeldOptions {
+    pub fn new(weld_tolerance: f64, crease_angle: f64) -> WeldOptions {
+        WeldOptions { weld_tolerance, crease_angle }
+    }
+}
+
+impl Default for WeldOptions {
+    // Fully smooth, matching the old all-pairs comparison
+    fn default() -> WeldOptions {
+        WeldOptions::new(0.0001, 180.0)
+    }
+}
+
+// Merges triangle corners that lie within `weld_tolerance` of each other and returns, per triangle,
+// the vertex normals of its three corners. Neighbouring face normals are weighted by the corner angle
+// and only included when they are within `crease_angle` degrees of the corner's own face normal.
+pub fn compute_vertex_normals(triangles: &[[Vector3; 3]], face_normals: &[Vector3], options: &WeldOptions) -> Vec<[Vector3; 3]> {
+    let corner_vertices: Vec<usize> = weld_corners(triangles, options.weld_tolerance);
+
+    let vertex_count: usize = corner_vertices.iter().max().map_or(0, |&max: &usize| max + 1);
+    let mut vertex_corners: Vec<Vec<usize>> = vec![vec![]; vertex_count];
+    for (corner, &vertex) in corner_vertices.iter().enumerate() {
+        vertex_corners[vertex].push(corner);
+    }
+
+    let corner_weights: Vec<f64> = triangles.par_iter()
+        .flat_map_iter(|tri: &[Vector3; 3]| (0..3).map(move |i: usize| corner_angle(tri, i)))
+        .collect();
+    let cos_crease: f64 = options.crease_angle.clamp(0.0, 180.0).to_radians().cos();
+
+    (0..triangles.len()).into_par_iter().map(|tri_index: usize| {
+        let face_normal: Vector3 = face_normals[tri_index];
+        let mut normals: [Vector3; 3] = [face_normal; 3];
+        for (i, normal) in normals.iter_mut().enumerate() {
+            let corner: usize = 3 * tri_index + i;
+            let mut accumulated: Vector3 = Vector3::zero();
+            for &other_corner in &vertex_corners[corner_vertices[corner]] {
+                let other_normal: Vector3 = face_normals[other_corner / 3];
+                if other_corner == corner || face_normal * other_normal >= cos_crease {
+                    accumulated += corner_weights[other_corner] * other_normal;
+                }
+            }
+            if accumulated != Vector3::zero() {
+                *normal = accumulated.normalize();
+            }
+        }
+        normals
+    }).collect()
+}
+
+// Assigns every corner (3 per triangle) a welded vertex index using a spatial hash with cells the
+// size of the tolerance, so only the surrounding 27 cells need to be searched per corner
+fn weld_corners(triangles: &[[Vector3; 3]], weld_tolerance: f64) -> Vec<usize> {
+    let tolerance: f64 = f64::max(weld_tolerance, 1e-12);
+    let cell_of = |p: Vector3| -> (i64, i64, i64) {
+        ((p.x / tolerance).floor() as i64, (p.y / tolerance).floor() as i64, (p.z / tolerance).floor() as i64)
+    };
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut vertex_positions: Vec<Vector3> = vec![];
+    let mut corner_vertices: Vec<usize> = Vec::with_capacity(3 * triangles.len());
+
+    for &position in triangles.iter().flatten() {
+        let cell: (i64, i64, i64) = cell_of(position);
+        let mut found: Option<usize> = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(vertices) = grid.get(&(cell.0 + dx, cell.1 + dy, cell.2 + dz)) {
+                        if let Some(&vertex) = vertices.iter().find(|&&v: &&usize| (vertex_positions[v] - position).magnitude() < tolerance) {
+                            found = Some(vertex);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let vertex: usize = match found {
+            Some(vertex) => vertex,
+            None => {
+                vertex_positions.push(position);
+                grid.entry(cell).or_default().push(vertex_positions.len() - 1);
+                vertex_positions.len() - 1
+            }
+        };
+        corner_vertices.push(vertex);
+    }
+
+    corner_vertices
+}
+
+fn corner_angle(tri: &[Vector3; 3], i: usize) -> f64 {
+    let edge1: Vector3 = (tri[(i + 1) % 3] - tri[i]).normalize();
+    let edge2: Vector3 = (tri[(i + 2) % 3] - tri[i]).normalize();
+    (edge1 * edge2).clamp(-1.0, 1.0).acos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    // Outward face normals of loose triangles, as an STL file stores them
+    fn face_normals(triangles: &[[Vector3; 3]]) -> Vec<Vector3> {
+        triangles.iter().map(|[a, b, c]: &[Vector3; 3]| {
+            let normal: Vector3 = (*b - *a).cross(&(*c - *a)).normalize();
+            if normal * (*a + *b + *c) < 0.0 { -1.0 * normal } else { normal }
+        }).collect()
+    }
+
+    // Every face split into two triangles that share no vertices with the other faces
+    fn cube() -> Vec<[Vector3; 3]> {
+        let mut triangles: Vec<[Vector3; 3]> = vec![];
+        for axis in 0..3 {
+            for side in [-1.0, 1.0] {
+                let corner = |u: f64, v: f64| -> Vector3 {
+                    match axis {
+                        0 => Vector3::new(side, u, v),
+                        1 => Vector3::new(u, side, v),
+                        _ => Vector3::new(u, v, side)
+                    }
+                };
+                triangles.push([corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0)]);
+                triangles.push([corner(-1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)]);
+            }
+        }
+        triangles
+    }
+
+    // Unit UV sphere of loose triangles, the caps use one triangle per segment
+    fn sphere(rings: usize, segments: usize) -> Vec<[Vector3; 3]> {
+        let point = |ring: usize, segment: usize| -> Vector3 {
+            let (theta, phi) = (PI * ring as f64 / rings as f64, 2.0 * PI * segment as f64 / segments as f64);
+            Vector3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+        };
+        let mut triangles: Vec<[Vector3; 3]> = vec![];
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let (a, b) = (point(ring, segment), point(ring, segment + 1));
+                let (c, d) = (point(ring + 1, segment + 1), point(ring + 1, segment));
+                if ring != 0 {
+                    triangles.push([a, b, c]);
+                }
+                if ring != rings - 1 {
+                    triangles.push([a, c, d]);
+                }
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn cube_keeps_hard_edges_below_the_crease_angle() {
+        let triangles: Vec<[Vector3; 3]> = cube();
+        let normals: Vec<Vector3> = face_normals(&triangles);
+
+        let creased: Vec<[Vector3; 3]> = compute_vertex_normals(&triangles, &normals, &WeldOptions::new(0.0001, 60.0));
+        for (corners, face_normal) in creased.iter().zip(&normals) {
+            for corner in corners {
+                assert!(corner.distance(*face_normal) < 1e-9);
+            }
+        }
+
+        // Fully smooth, every corner points away from the center
+        let smooth: Vec<[Vector3; 3]> = compute_vertex_normals(&triangles, &normals, &WeldOptions::default());
+        for (corners, triangle) in smooth.iter().zip(&triangles) {
+            for (corner, position) in corners.iter().zip(triangle) {
+                assert!(corner.distance(position.normalize()) < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn split_sphere_welds_to_smooth_normals() {
+        let triangles: Vec<[Vector3; 3]> = sphere(16, 32);
+        let normals: Vec<Vector3> = face_normals(&triangles);
+        let vertex_normals: Vec<[Vector3; 3]> = compute_vertex_normals(&triangles, &normals, &WeldOptions::new(0.0001, 60.0));
+
+        let mut shared: Vec<(Vector3, Vector3)> = vec![];
+        for (corners, triangle) in vertex_normals.iter().zip(&triangles) {
+            for (&normal, &position) in corners.iter().zip(triangle) {
+                // Close to the true sphere normal, and the same for every triangle meeting at the point
+                assert!(normal * position > 0.999);
+                if let Some((_, first)) = shared.iter().find(|(point, _)| point.distance(position) < 1e-9) {
+                    assert!(normal.distance(*first) < 1e-9);
+                } else {
+                    shared.push((position, normal));
+                }
+            }
+        }
+        // Welding leaves one vertex per sphere point, the seam and the poles included
+        assert_eq!(shared.len(), 15 * 32 + 2);
+        assert_eq!(weld_corners(&triangles, 0.0001).into_iter().max(), Some(15 * 32 + 1));
+    }
+}